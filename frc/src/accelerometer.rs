@@ -0,0 +1,147 @@
+//! Accelerometers.
+//!
+//! The roboRIO has a built-in 3-axis accelerometer, accessed through [`BuiltInAccelerometer`].
+//! External accelerometers can implement the [`Accelerometer`] trait, so code which reads
+//! acceleration can be written once for either.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    convert::Infallible,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use uom::si::{
+    acceleration::standard_gravity,
+    f64::{Acceleration, Angle},
+};
+
+/// Acceleration along 3 axes. For the built-in accelerometer, x points to the right of the
+/// roboRIO's label, y points up the label, and z points out of the label.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccelerationVector {
+    pub x: Acceleration,
+    pub y: Acceleration,
+    pub z: Acceleration,
+}
+
+impl AccelerationVector {
+    /// Gets the tilt around the y axis, assuming the only acceleration is gravity.
+    /// Zero when the z axis points straight up.
+    #[must_use]
+    pub fn pitch(&self) -> Angle {
+        let yz = (self.y * self.y + self.z * self.z).sqrt();
+        self.x.atan2(yz)
+    }
+
+    /// Gets the tilt around the x axis, assuming the only acceleration is gravity.
+    /// Zero when the z axis points straight up.
+    #[must_use]
+    pub fn roll(&self) -> Angle {
+        self.y.atan2(self.z)
+    }
+}
+
+/// A 3-axis accelerometer.
+pub trait Accelerometer {
+    type Error;
+
+    /// Gets the current acceleration.
+    /// # Errors
+    /// Returns an error if the accelerometer can't be read.
+    fn acceleration(&mut self) -> Result<AccelerationVector, Self::Error>;
+}
+
+/// The range of accelerations the built-in accelerometer measures. Smaller ranges
+/// are more precise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelerometerRange {
+    /// -2 to 2 g
+    G2,
+    /// -4 to 4 g
+    G4,
+    /// -8 to 8 g
+    G8,
+}
+
+impl From<AccelerometerRange> for wpihal_sys::HAL_AccelerometerRange {
+    fn from(value: AccelerometerRange) -> Self {
+        match value {
+            AccelerometerRange::G2 => wpihal_sys::HAL_AccelerometerRange_HAL_AccelerometerRange_k2G,
+            AccelerometerRange::G4 => wpihal_sys::HAL_AccelerometerRange_HAL_AccelerometerRange_k4G,
+            AccelerometerRange::G8 => wpihal_sys::HAL_AccelerometerRange_HAL_AccelerometerRange_k8G,
+        }
+    }
+}
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The roboRIO's built-in accelerometer. Only one can exist at a time.
+pub struct BuiltInAccelerometer {
+    range: AccelerometerRange,
+}
+
+impl BuiltInAccelerometer {
+    /// Activates the built-in accelerometer with the given range.
+    /// Returns `None` if it is already in use.
+    #[must_use]
+    pub fn take(range: AccelerometerRange) -> Option<Self> {
+        let previously_taken = TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            let mut accelerometer = Self { range };
+            accelerometer.set_range(range);
+            Some(accelerometer)
+        }
+    }
+
+    /// Gets the range of the accelerometer.
+    #[must_use]
+    pub fn range(&self) -> AccelerometerRange {
+        self.range
+    }
+
+    /// Sets the range of the accelerometer.
+    pub fn set_range(&mut self, range: AccelerometerRange) {
+        // the range can only be changed while the accelerometer is inactive
+        unsafe {
+            wpihal_sys::HAL_SetAccelerometerActive(0);
+            wpihal_sys::HAL_SetAccelerometerRange(range.into());
+            wpihal_sys::HAL_SetAccelerometerActive(1);
+        }
+        self.range = range;
+    }
+
+    /// Gets the current acceleration.
+    #[must_use]
+    pub fn get(&self) -> AccelerationVector {
+        let (x, y, z) = unsafe {
+            (
+                wpihal_sys::HAL_GetAccelerometerX(),
+                wpihal_sys::HAL_GetAccelerometerY(),
+                wpihal_sys::HAL_GetAccelerometerZ(),
+            )
+        };
+        AccelerationVector {
+            x: Acceleration::new::<standard_gravity>(x),
+            y: Acceleration::new::<standard_gravity>(y),
+            z: Acceleration::new::<standard_gravity>(z),
+        }
+    }
+}
+
+impl Accelerometer for BuiltInAccelerometer {
+    type Error = Infallible;
+
+    fn acceleration(&mut self) -> Result<AccelerationVector, Infallible> {
+        Ok(self.get())
+    }
+}
+
+impl Drop for BuiltInAccelerometer {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_SetAccelerometerActive(0) };
+        TAKEN.store(false, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,141 @@
+//! Analog accumulators.
+//!
+//! The FPGA has 2 accumulators, on analog inputs 0 and 1, which integrate every sample of
+//! their input. Each sample is offset by a center value before it is added, and samples
+//! within a deadband of the center are ignored. This is how analog gyros measure angle.
+
+#![allow(clippy::module_name_repetitions)]
+
+use super::{AnalogError, AnalogInput};
+
+/// The accumulated value and number of samples, read together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccumulatorOutput {
+    pub value: i64,
+    pub count: i64,
+}
+
+/// An accumulator on analog input N. Only inputs 0 and 1 have accumulators.
+pub struct Accumulator<const N: u8> {
+    input: AnalogInput<N>,
+}
+
+impl<const N: u8> Accumulator<N> {
+    const _ACCUMULATOR: () = assert!(N < 2);
+
+    /// Starts accumulating an analog input, from zero.
+    /// # Errors
+    /// Returns an error if wpihal fails to initialize the accumulator.
+    /// The input is returned with the error.
+    pub fn new(input: AnalogInput<N>) -> Result<Self, (AnalogError, AnalogInput<N>)> {
+        let () = Self::_ACCUMULATOR;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_InitAccumulator(input.handle(), std::ptr::from_mut(&mut status)) };
+        match AnalogError::from_status(status) {
+            Ok(()) => Ok(Self { input }),
+            Err(e) => Err((e, input)),
+        }
+    }
+
+    /// Resets the accumulated value and count to zero.
+    /// # Errors
+    /// Returns an error if wpihal fails to reset the accumulator.
+    pub fn reset(&mut self) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_ResetAccumulator(self.input.handle(), std::ptr::from_mut(&mut status));
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Sets the raw value which is subtracted from each sample before it is accumulated.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the accumulator.
+    pub fn set_center(&mut self, center: i32) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAccumulatorCenter(
+                self.input.handle(),
+                center,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Sets the raw distance from the center within which samples are ignored.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the accumulator.
+    pub fn set_deadband(&mut self, deadband: i32) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAccumulatorDeadband(
+                self.input.handle(),
+                deadband,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Gets the accumulated value.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the accumulator.
+    pub fn value(&self) -> Result<i64, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let value = unsafe {
+            wpihal_sys::HAL_GetAccumulatorValue(
+                self.input.handle(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        AnalogError::from_status(status)?;
+        Ok(value)
+    }
+
+    /// Gets the number of samples accumulated.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the accumulator.
+    pub fn count(&self) -> Result<i64, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let count = unsafe {
+            wpihal_sys::HAL_GetAccumulatorCount(
+                self.input.handle(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        AnalogError::from_status(status)?;
+        Ok(count)
+    }
+
+    /// Gets the accumulated value and number of samples atomically, e.g. to compute
+    /// the average sample.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the accumulator.
+    pub fn output(&self) -> Result<AccumulatorOutput, AnalogError> {
+        let mut output = AccumulatorOutput { value: 0, count: 0 };
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_GetAccumulatorOutput(
+                self.input.handle(),
+                std::ptr::from_mut(&mut output.value),
+                std::ptr::from_mut(&mut output.count),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)?;
+        Ok(output)
+    }
+
+    /// Gets the analog input being accumulated.
+    #[must_use]
+    pub fn input(&self) -> &AnalogInput<N> {
+        &self.input
+    }
+
+    /// Stops using the accumulator, returning the analog input.
+    #[must_use]
+    pub fn into_input(self) -> AnalogInput<N> {
+        self.input
+    }
+}
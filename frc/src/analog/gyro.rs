@@ -0,0 +1,266 @@
+//! Analog gyros, such as the Analog Devices ADXRS652 in the KOP.
+//!
+//! An analog gyro outputs a voltage proportional to its rate of rotation, which is integrated
+//! by an [`Accumulator`](super::Accumulator) to measure its angle. As the zero-rate voltage
+//! varies between gyros, it must be calibrated while the robot is still, which takes 5 seconds.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use uom::si::{
+    angle::degree,
+    angular_velocity::degree_per_second,
+    electric_potential::volt,
+    f64::{Angle, AngularVelocity, ElectricPotential},
+};
+
+use super::{AnalogError, AnalogInput};
+use crate::gyro::Gyro;
+
+/// The sensitivity of the ADXRS652, which is used by default
+pub const DEFAULT_VOLTS_PER_DEGREE_PER_SECOND: f64 = 0.007;
+
+/// The result of calibrating a gyro, which can be saved and restored with
+/// [`AnalogGyro::set_calibration`] to skip calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GyroCalibration {
+    /// The fractional part of the zero-rate value, in raw ADC units
+    pub offset: f64,
+    /// The integer part of the zero-rate value, in raw ADC units
+    pub center: i32,
+}
+
+/// Owns the HAL gyro and its input, so a calibration running on another thread
+/// can keep them alive if the gyro is dropped.
+struct Inner<const N: u8> {
+    handle: wpihal_sys::HAL_GyroHandle,
+    _input: AnalogInput<N>,
+    // set while a calibration thread is running, which may outlive its future
+    calibrating: AtomicBool,
+}
+
+impl<const N: u8> Drop for Inner<N> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeAnalogGyro(self.handle) };
+    }
+}
+
+/// An analog gyro on analog input N. Only inputs 0 and 1 have the accumulator it needs.
+pub struct AnalogGyro<const N: u8> {
+    inner: Arc<Inner<N>>,
+    sensitivity: f64,
+}
+
+impl<const N: u8> AnalogGyro<N> {
+    const _ACCUMULATOR: () = assert!(N < 2);
+
+    /// Creates an uncalibrated gyro. Call [`AnalogGyro::calibrate`] while the robot is
+    /// still, or restore a previous calibration with [`AnalogGyro::set_calibration`].
+    /// # Errors
+    /// Returns an error if wpihal fails to initialize the gyro.
+    /// The input is returned with the error.
+    pub fn new(input: AnalogInput<N>) -> Result<Self, (AnalogError, AnalogInput<N>)> {
+        let () = Self::_ACCUMULATOR;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeAnalogGyro(
+                input.handle(),
+                c"".as_ptr(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        if let Err(e) = AnalogError::from_status(status) {
+            return Err((e, input));
+        }
+        unsafe { wpihal_sys::HAL_SetupAnalogGyro(handle, std::ptr::from_mut(&mut status)) };
+        if let Err(e) = AnalogError::from_status(status) {
+            unsafe { wpihal_sys::HAL_FreeAnalogGyro(handle) };
+            return Err((e, input));
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                handle,
+                _input: input,
+                calibrating: AtomicBool::new(false),
+            }),
+            sensitivity: DEFAULT_VOLTS_PER_DEGREE_PER_SECOND,
+        })
+    }
+
+    fn handle(&self) -> wpihal_sys::HAL_GyroHandle {
+        self.inner.handle
+    }
+
+    /// Fails if a calibration is still running, so it can't be raced.
+    fn check_not_calibrating(&self) -> Result<(), AnalogError> {
+        if self.inner.calibrating.load(Ordering::Acquire) {
+            Err(AnalogError::Calibrating)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Measures the zero-rate value of the gyro, which takes 5 seconds. The robot must be
+    /// still during calibration, e.g. while disabled. Calibration runs on its own thread,
+    /// so it doesn't block the executor.
+    ///
+    /// Dropping the future doesn't stop calibration. Until it finishes, calibrating,
+    /// restoring a calibration or resetting the gyro fail with [`AnalogError::Calibrating`].
+    /// # Errors
+    /// Returns [`AnalogError::Calibrating`] if a previous calibration is still running,
+    /// or an error if wpihal fails to calibrate the gyro.
+    pub async fn calibrate(&mut self) -> Result<(), AnalogError> {
+        if self
+            .inner
+            .calibrating
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(AnalogError::Calibrating);
+        }
+        let inner = Arc::clone(&self.inner);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let mut status = wpihal_sys::HAL_SUCCESS;
+            unsafe {
+                wpihal_sys::HAL_CalibrateAnalogGyro(inner.handle, std::ptr::from_mut(&mut status));
+            }
+            inner.calibrating.store(false, Ordering::Release);
+            let _ = tx.send(status);
+        });
+        let status = rx.await.map_err(|_| AnalogError::CalibrationFailed)?;
+        AnalogError::from_status(status)
+    }
+
+    /// Gets the current calibration of the gyro.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the calibration.
+    pub fn calibration(&self) -> Result<GyroCalibration, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let offset = unsafe {
+            wpihal_sys::HAL_GetAnalogGyroOffset(self.handle(), std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        let center = unsafe {
+            wpihal_sys::HAL_GetAnalogGyroCenter(self.handle(), std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(GyroCalibration { offset, center })
+    }
+
+    /// Restores a calibration from [`AnalogGyro::calibration`], and resets the angle to zero.
+    /// # Errors
+    /// Returns [`AnalogError::Calibrating`] if a calibration is still running,
+    /// or an error if wpihal fails to configure the gyro.
+    pub fn set_calibration(&mut self, calibration: GyroCalibration) -> Result<(), AnalogError> {
+        self.check_not_calibrating()?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogGyroParameters(
+                self.handle(),
+                self.sensitivity,
+                calibration.offset,
+                calibration.center,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)?;
+        self.reset()
+    }
+
+    /// Gets the sensitivity of the gyro, in volts per degree per second.
+    #[must_use]
+    pub fn volts_per_degree_per_second(&self) -> f64 {
+        self.sensitivity
+    }
+
+    /// Sets the sensitivity of the gyro, in volts per degree per second.
+    /// The default is [`DEFAULT_VOLTS_PER_DEGREE_PER_SECOND`].
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the gyro.
+    pub fn set_volts_per_degree_per_second(&mut self, sensitivity: f64) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogGyroVoltsPerDegreePerSecond(
+                self.handle(),
+                sensitivity,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)?;
+        self.sensitivity = sensitivity;
+        Ok(())
+    }
+
+    /// Sets the voltage from the zero-rate value within which the gyro is treated as still,
+    /// to reduce drift.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the gyro.
+    pub fn set_deadband(&mut self, deadband: ElectricPotential) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogGyroDeadband(
+                self.handle(),
+                deadband.get::<volt>(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Resets the angle of the gyro to zero.
+    /// # Errors
+    /// Returns [`AnalogError::Calibrating`] if a calibration is still running,
+    /// or an error if wpihal fails to reset the gyro.
+    pub fn reset(&mut self) -> Result<(), AnalogError> {
+        self.check_not_calibrating()?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_ResetAnalogGyro(self.handle(), std::ptr::from_mut(&mut status)) };
+        AnalogError::from_status(status)
+    }
+
+    /// Gets the angle turned since the gyro was last reset. Positive angles are clockwise,
+    /// and the angle is continuous rather than wrapping at 360 degrees.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the gyro.
+    pub fn angle(&self) -> Result<Angle, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let angle = unsafe {
+            wpihal_sys::HAL_GetAnalogGyroAngle(self.handle(), std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(Angle::new::<degree>(angle))
+    }
+
+    /// Gets the rate of rotation of the gyro. Positive rates are clockwise.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the gyro.
+    pub fn rate(&self) -> Result<AngularVelocity, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let rate = unsafe {
+            wpihal_sys::HAL_GetAnalogGyroRate(self.handle(), std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(AngularVelocity::new::<degree_per_second>(rate))
+    }
+}
+
+impl<const N: u8> Gyro for AnalogGyro<N> {
+    type Error = AnalogError;
+
+    fn angle(&mut self) -> Result<Angle, AnalogError> {
+        AnalogGyro::angle(self)
+    }
+
+    fn rate(&mut self) -> Result<AngularVelocity, AnalogError> {
+        AnalogGyro::rate(self)
+    }
+
+    fn reset(&mut self) -> Result<(), AnalogError> {
+        AnalogGyro::reset(self)
+    }
+}
@@ -0,0 +1,288 @@
+//! Analog input channels.
+//!
+//! Every analog input is sampled by the same ADC, whose rate is set with [`set_sample_rate`].
+//! Each channel can then oversample and average its readings in the FPGA: 2^oversample bits
+//! samples are added together, and 2^average bits of those sums are averaged.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use uom::si::{
+    electric_potential::volt,
+    f64::{ElectricPotential, Frequency},
+    frequency::hertz,
+};
+
+use super::{initialize_input, AnalogError};
+
+/// Sets the rate at which each analog input is sampled. This is shared between all channels.
+/// The default is 50 kHz per channel.
+/// # Errors
+/// Returns [`AnalogError::ParameterOutOfRange`] if the rate is too high or too low.
+pub fn set_sample_rate(rate: Frequency) -> Result<(), AnalogError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe {
+        wpihal_sys::HAL_SetAnalogSampleRate(rate.get::<hertz>(), std::ptr::from_mut(&mut status));
+    }
+    AnalogError::from_status(status)
+}
+
+/// Gets the rate at which each analog input is sampled.
+/// # Errors
+/// Returns an error if wpihal fails to read the rate.
+pub fn sample_rate() -> Result<Frequency, AnalogError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let rate = unsafe { wpihal_sys::HAL_GetAnalogSampleRate(std::ptr::from_mut(&mut status)) };
+    AnalogError::from_status(status)?;
+    Ok(Frequency::new::<hertz>(rate))
+}
+
+/// Represents an unused analog input channel.
+/// N is the channel number: 0..=3 are on the built-in analog port, 4..=7 are on the MXP.
+pub struct AnalogChannel<const N: u8> {
+    _private: (),
+}
+
+impl<const N: u8> AnalogChannel<N> {
+    const _VALID: () = assert!(N < 8);
+
+    fn new() -> Self {
+        let () = Self::_VALID;
+        Self { _private: () }
+    }
+
+    /// Configures the channel as an analog input.
+    /// # Panics
+    /// Panics if the channel can't be allocated. See [`AnalogChannel::try_into_input`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn into_input(self) -> AnalogInput<N> {
+        self.try_into_input()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize AI {N}: {e}"))
+    }
+
+    /// Configures the channel as an analog input.
+    /// # Errors
+    /// Returns [`AnalogError::ResourceAlreadyAllocated`] if the channel is already in use.
+    /// The channel is returned with the error.
+    pub fn try_into_input(self) -> Result<AnalogInput<N>, (AnalogError, Self)> {
+        match initialize_input(N) {
+            Ok(handle) => Ok(AnalogInput { handle }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+pub type Ai0 = AnalogChannel<0>;
+pub type Ai1 = AnalogChannel<1>;
+pub type Ai2 = AnalogChannel<2>;
+pub type Ai3 = AnalogChannel<3>;
+pub type Ai4 = AnalogChannel<4>;
+pub type Ai5 = AnalogChannel<5>;
+pub type Ai6 = AnalogChannel<6>;
+pub type Ai7 = AnalogChannel<7>;
+
+pub struct AnalogPort {
+    pub ai0: Ai0,
+    pub ai1: Ai1,
+    pub ai2: Ai2,
+    pub ai3: Ai3,
+    pub ai4: Ai4,
+    pub ai5: Ai5,
+    pub ai6: Ai6,
+    pub ai7: Ai7,
+}
+
+static PORT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl AnalogPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = PORT_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                ai0: Ai0::new(),
+                ai1: Ai1::new(),
+                ai2: Ai2::new(),
+                ai3: Ai3::new(),
+                ai4: Ai4::new(),
+                ai5: Ai5::new(),
+                ai6: Ai6::new(),
+                ai7: Ai7::new(),
+            })
+        }
+    }
+}
+
+/// An analog input on channel N.
+pub struct AnalogInput<const N: u8> {
+    handle: wpihal_sys::HAL_AnalogInputHandle,
+}
+
+impl<const N: u8> AnalogInput<N> {
+    pub(crate) fn handle(&self) -> wpihal_sys::HAL_AnalogInputHandle {
+        self.handle
+    }
+
+    /// Gets the most recent sample as a voltage.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input.
+    pub fn voltage(&self) -> Result<ElectricPotential, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let voltage = unsafe {
+            wpihal_sys::HAL_GetAnalogVoltage(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(ElectricPotential::new::<volt>(voltage))
+    }
+
+    /// Gets the oversampled and averaged value as a voltage. This is less noisy than
+    /// [`AnalogInput::voltage`], but updates less often.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input.
+    pub fn average_voltage(&self) -> Result<ElectricPotential, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let voltage = unsafe {
+            wpihal_sys::HAL_GetAnalogAverageVoltage(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(ElectricPotential::new::<volt>(voltage))
+    }
+
+    /// Gets the most recent sample as a raw 12-bit ADC value.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input.
+    pub fn value(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let value =
+            unsafe { wpihal_sys::HAL_GetAnalogValue(self.handle, std::ptr::from_mut(&mut status)) };
+        AnalogError::from_status(status)?;
+        Ok(value)
+    }
+
+    /// Gets the oversampled and averaged value. This is scaled up by 2^oversample bits,
+    /// as oversampled values are added together rather than averaged.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input.
+    pub fn average_value(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let value = unsafe {
+            wpihal_sys::HAL_GetAnalogAverageValue(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(value)
+    }
+
+    /// Converts a raw ADC value from this channel to a voltage, using its calibration.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the calibration.
+    pub fn value_to_voltage(&self, value: i32) -> Result<ElectricPotential, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let voltage = unsafe {
+            wpihal_sys::HAL_GetAnalogValueToVolts(
+                self.handle,
+                value,
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        AnalogError::from_status(status)?;
+        Ok(ElectricPotential::new::<volt>(voltage))
+    }
+
+    /// Sets the number of averaging bits: 2^bits samples are averaged.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the input.
+    pub fn set_average_bits(&mut self, bits: u8) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogAverageBits(
+                self.handle,
+                bits.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Gets the number of averaging bits.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the configuration.
+    pub fn average_bits(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let bits = unsafe {
+            wpihal_sys::HAL_GetAnalogAverageBits(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(bits)
+    }
+
+    /// Sets the number of oversampling bits: 2^bits samples are added together.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the input.
+    pub fn set_oversample_bits(&mut self, bits: u8) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogOversampleBits(
+                self.handle,
+                bits.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Gets the number of oversampling bits.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the configuration.
+    pub fn oversample_bits(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let bits = unsafe {
+            wpihal_sys::HAL_GetAnalogOversampleBits(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(bits)
+    }
+
+    /// Gets the factory calibrated weight of one ADC LSB, in nanovolts.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the calibration.
+    pub fn lsb_weight(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let weight = unsafe {
+            wpihal_sys::HAL_GetAnalogLSBWeight(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(weight)
+    }
+
+    /// Gets the factory calibrated offset of the ADC, in nanovolts.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the calibration.
+    pub fn offset(&self) -> Result<i32, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let offset = unsafe {
+            wpihal_sys::HAL_GetAnalogOffset(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(offset)
+    }
+
+    /// Frees the analog input, returning its channel.
+    #[must_use]
+    pub fn into_channel(self) -> AnalogChannel<N> {
+        let this = ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_FreeAnalogInputPort(this.handle) };
+        AnalogChannel { _private: () }
+    }
+}
+
+impl<const N: u8> Drop for AnalogInput<N> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeAnalogInputPort(self.handle) };
+    }
+}
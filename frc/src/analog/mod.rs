@@ -0,0 +1,96 @@
+//! Analog inputs and outputs on the roboRIO.
+//! Input channels 0..=3 are on the built-in analog port, and channels 4..=7 are on the MXP.
+//! Output channels 0 and 1 are on the MXP.
+
+pub mod accumulator;
+pub use accumulator::*;
+pub mod gyro;
+pub use gyro::*;
+pub mod input;
+pub use input::*;
+pub mod output;
+pub use output::*;
+pub mod scaled;
+pub use scaled::*;
+pub mod trigger;
+pub use trigger::*;
+
+use embedded_hal::digital::ErrorKind;
+use thiserror::Error;
+
+use crate::error::HalError;
+use crate::reactor::dio::InterruptError;
+
+#[derive(Error, Debug)]
+pub enum AnalogError {
+    #[error("channel is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("channel is out of range")]
+    OutOfRange,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error("lower limit is above upper limit")]
+    LimitOrder,
+    #[error("all analog triggers are in use")]
+    NoAvailableTriggers,
+    #[error("no resources are available")]
+    NoAvailableResources,
+    #[error("all interrupts are in use")]
+    NoAvailableInterrupts,
+    #[error("gyro is being calibrated")]
+    Calibrating,
+    #[error("gyro calibration stopped unexpectedly")]
+    CalibrationFailed,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl AnalogError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(AnalogError::ResourceAlreadyAllocated),
+            wpihal_sys::RESOURCE_OUT_OF_RANGE => Err(AnalogError::OutOfRange),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(AnalogError::ParameterOutOfRange),
+            wpihal_sys::ANALOG_TRIGGER_LIMIT_ORDER_ERROR => Err(AnalogError::LimitOrder),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(AnalogError::NoAvailableResources),
+            a => Err(AnalogError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+impl From<InterruptError> for AnalogError {
+    fn from(value: InterruptError) -> Self {
+        match value {
+            InterruptError::NoAvailableInterrupt => AnalogError::NoAvailableInterrupts,
+            InterruptError::BadHandle => {
+                AnalogError::Hal(HalError::new(wpihal_sys::HAL_HANDLE_ERROR))
+            }
+            InterruptError::Fpga(e) => AnalogError::Hal(e),
+        }
+    }
+}
+
+impl embedded_hal::digital::Error for AnalogError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// Initializes an analog input on a runtime channel, checking the channel is valid first.
+fn initialize_input(channel: u8) -> Result<wpihal_sys::HAL_AnalogInputHandle, AnalogError> {
+    let valid = unsafe { wpihal_sys::HAL_CheckAnalogInputChannel(channel.into()) };
+    if valid == 0 {
+        return Err(AnalogError::OutOfRange);
+    }
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let handle = unsafe {
+        wpihal_sys::HAL_InitializeAnalogInputPort(
+            wpihal_sys::HAL_GetPort(channel.into()),
+            c"".as_ptr(),
+            std::ptr::from_mut(&mut status),
+        )
+    };
+    AnalogError::from_status(status)?;
+    Ok(handle)
+}
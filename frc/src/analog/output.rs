@@ -0,0 +1,151 @@
+//! Analog output channels.
+//!
+//! The MXP has 2 analog outputs, which can output 0 to 5 V.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use uom::si::{electric_potential::volt, f64::ElectricPotential};
+
+use super::AnalogError;
+
+/// The highest voltage an analog output can output
+pub const MAX_OUTPUT_VOLTAGE: f64 = 5.0;
+
+/// Something whose output is set as a voltage, such as an analog output or an
+/// analog-input motor driver.
+pub trait SetVoltage {
+    type Error;
+
+    /// Sets the output voltage.
+    /// # Errors
+    /// Returns an error if the output can't be set.
+    fn set_voltage(&mut self, voltage: ElectricPotential) -> Result<(), Self::Error>;
+}
+
+/// Represents an unused analog output channel on the MXP. N is the channel number, 0 or 1.
+pub struct AnalogOutputChannel<const N: u8> {
+    _private: (),
+}
+
+impl<const N: u8> AnalogOutputChannel<N> {
+    const _VALID: () = assert!(N < 2);
+
+    fn new() -> Self {
+        let () = Self::_VALID;
+        Self { _private: () }
+    }
+
+    /// Configures the channel as an analog output, at 0 V.
+    /// # Panics
+    /// Panics if the channel can't be allocated. See [`AnalogOutputChannel::try_into_output`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn into_output(self) -> AnalogOutput<N> {
+        self.try_into_output()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize AO {N}: {e}"))
+    }
+
+    /// Configures the channel as an analog output, at 0 V.
+    /// # Errors
+    /// Returns [`AnalogError::ResourceAlreadyAllocated`] if the channel is already in use.
+    /// The channel is returned with the error.
+    pub fn try_into_output(self) -> Result<AnalogOutput<N>, (AnalogError, Self)> {
+        let valid = unsafe { wpihal_sys::HAL_CheckAnalogOutputChannel(N.into()) };
+        if valid == 0 {
+            return Err((AnalogError::OutOfRange, self));
+        }
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeAnalogOutputPort(
+                wpihal_sys::HAL_GetPort(N.into()),
+                c"".as_ptr(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        match AnalogError::from_status(status) {
+            Ok(()) => Ok(AnalogOutput { handle }),
+            Err(e) => Err((e, self)),
+        }
+    }
+}
+
+pub type Ao0 = AnalogOutputChannel<0>;
+pub type Ao1 = AnalogOutputChannel<1>;
+
+pub struct AnalogOutputPort {
+    pub ao0: Ao0,
+    pub ao1: Ao1,
+}
+
+static PORT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl AnalogOutputPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = PORT_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                ao0: Ao0::new(),
+                ao1: Ao1::new(),
+            })
+        }
+    }
+}
+
+/// An analog output on MXP channel N.
+pub struct AnalogOutput<const N: u8> {
+    handle: wpihal_sys::HAL_AnalogOutputHandle,
+}
+
+impl<const N: u8> AnalogOutput<N> {
+    /// Gets the voltage being output.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the output.
+    pub fn voltage(&self) -> Result<ElectricPotential, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let voltage = unsafe {
+            wpihal_sys::HAL_GetAnalogOutput(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(ElectricPotential::new::<volt>(voltage))
+    }
+
+    /// Frees the analog output, returning its channel.
+    #[must_use]
+    pub fn into_channel(self) -> AnalogOutputChannel<N> {
+        let this = ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_FreeAnalogOutputPort(this.handle) };
+        AnalogOutputChannel { _private: () }
+    }
+}
+
+impl<const N: u8> SetVoltage for AnalogOutput<N> {
+    type Error = AnalogError;
+
+    /// Sets the voltage being output, clamped to 0 to 5 V. NaN is treated as 0 V.
+    fn set_voltage(&mut self, voltage: ElectricPotential) -> Result<(), AnalogError> {
+        let voltage = voltage.get::<volt>();
+        let voltage = if voltage.is_nan() {
+            0.0
+        } else {
+            voltage.clamp(0.0, MAX_OUTPUT_VOLTAGE)
+        };
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogOutput(self.handle, voltage, std::ptr::from_mut(&mut status));
+        }
+        AnalogError::from_status(status)
+    }
+}
+
+impl<const N: u8> Drop for AnalogOutput<N> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeAnalogOutputPort(self.handle) };
+    }
+}
@@ -0,0 +1,93 @@
+//! Sensors which output a voltage proportional to what they measure.
+//!
+//! These sensors are ratiometric: their output scales with their supply voltage, so readings
+//! are divided by the roboRIO's 5 V rail voltage rather than a nominal 5 V.
+
+#![allow(clippy::module_name_repetitions)]
+
+use uom::si::{
+    electric_potential::volt,
+    f64::{Angle, ElectricPotential, Length},
+};
+
+use super::{AnalogError, AnalogInput};
+
+/// Reads the 5 V rail which powers analog sensors.
+fn supply_voltage() -> Result<ElectricPotential, AnalogError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let voltage = unsafe { wpihal_sys::HAL_GetUserVoltage5V(std::ptr::from_mut(&mut status)) };
+    AnalogError::from_status(status)?;
+    Ok(ElectricPotential::new::<volt>(voltage))
+}
+
+/// Reads an input as a fraction of the supply voltage, from 0 to 1.
+fn ratio<const N: u8>(input: &AnalogInput<N>) -> Result<f64, AnalogError> {
+    Ok((input.average_voltage()? / supply_voltage()?).value)
+}
+
+/// A potentiometer, which reports its position as an angle.
+pub struct Potentiometer<const N: u8> {
+    input: AnalogInput<N>,
+    full_range: Angle,
+    offset: Angle,
+}
+
+impl<const N: u8> Potentiometer<N> {
+    /// Creates a potentiometer which reads `offset` at 0 V, and `offset + full_range`
+    /// at the supply voltage. A negative range reverses the potentiometer's direction.
+    #[must_use]
+    pub fn new(input: AnalogInput<N>, full_range: Angle, offset: Angle) -> Self {
+        Self {
+            input,
+            full_range,
+            offset,
+        }
+    }
+
+    /// Gets the angle of the potentiometer.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input or the supply voltage.
+    pub fn angle(&self) -> Result<Angle, AnalogError> {
+        Ok(self.full_range * ratio(&self.input)? + self.offset)
+    }
+
+    /// Releases the analog input used by this potentiometer.
+    #[must_use]
+    pub fn into_input(self) -> AnalogInput<N> {
+        self.input
+    }
+}
+
+/// An analog ultrasonic rangefinder, such as a MaxBotix sensor, which reports distance.
+pub struct AnalogUltrasonic<const N: u8> {
+    input: AnalogInput<N>,
+    full_range: Length,
+    offset: Length,
+}
+
+impl<const N: u8> AnalogUltrasonic<N> {
+    /// Creates a rangefinder which reads `offset` at 0 V, and `offset + full_range`
+    /// at the supply voltage. E.g. the MaxBotix MB1013 outputs the supply voltage
+    /// at 5120 mm, with no offset.
+    #[must_use]
+    pub fn new(input: AnalogInput<N>, full_range: Length, offset: Length) -> Self {
+        Self {
+            input,
+            full_range,
+            offset,
+        }
+    }
+
+    /// Gets the distance to the nearest object.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the input or the supply voltage.
+    pub fn distance(&self) -> Result<Length, AnalogError> {
+        Ok(self.full_range * ratio(&self.input)? + self.offset)
+    }
+
+    /// Releases the analog input used by this rangefinder.
+    #[must_use]
+    pub fn into_input(self) -> AnalogInput<N> {
+        self.input
+    }
+}
@@ -0,0 +1,326 @@
+//! Analog triggers.
+//!
+//! An analog trigger compares an analog input against a lower and upper limit in the FPGA,
+//! turning it into a digital signal which can be read and awaited like an input pin.
+//! The roboRIO has 8 analog triggers.
+//!
+//! A trigger can also compare the output of a [`DutyCycleInput`] against limits given as
+//! duty cycles, e.g. to detect when an absolute encoder passes an angle.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::mem::ManuallyDrop;
+
+use embedded_hal::digital::{ErrorType, InputPin};
+use embedded_hal_async::digital::Wait;
+use uom::si::{electric_potential::volt, f64::ElectricPotential};
+
+use super::{AnalogError, AnalogInput};
+use crate::dio::DigitalSource;
+use crate::duty_cycle::DutyCycleInput;
+use crate::reactor::dio::{EdgeStream, EdgeType, Source};
+
+/// An input which an analog trigger can compare against its limits:
+/// an [`AnalogInput`], or a [`DutyCycleInput`].
+pub trait TriggerInput: crate::Sealed {
+    /// Allocates an analog trigger on this input.
+    /// # Errors
+    /// Returns an error if wpihal fails to allocate the trigger.
+    fn initialize_trigger(&self) -> Result<wpihal_sys::HAL_AnalogTriggerHandle, AnalogError>;
+}
+
+impl<const N: u8> crate::Sealed for AnalogInput<N> {}
+
+impl<const N: u8> TriggerInput for AnalogInput<N> {
+    fn initialize_trigger(&self) -> Result<wpihal_sys::HAL_AnalogTriggerHandle, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeAnalogTrigger(self.handle(), std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(handle)
+    }
+}
+
+impl<S: DigitalSource> crate::Sealed for DutyCycleInput<S> {}
+
+impl<S: DigitalSource> TriggerInput for DutyCycleInput<S> {
+    fn initialize_trigger(&self) -> Result<wpihal_sys::HAL_AnalogTriggerHandle, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeAnalogTriggerDutyCycle(
+                self.handle(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        AnalogError::from_status(status)?;
+        Ok(handle)
+    }
+}
+
+/// An analog trigger on an analog input or a duty cycle input.
+///
+/// The trigger's state goes high when the input rises above the upper limit, and low when it
+/// falls below the lower limit. Between the limits, the state is unchanged, so the gap between
+/// the limits acts as hysteresis. The [`InputPin`] and [`Wait`] implementations use this state.
+pub struct AnalogTrigger<I: TriggerInput> {
+    handle: wpihal_sys::HAL_AnalogTriggerHandle,
+    input: I,
+}
+
+impl<I: TriggerInput> AnalogTrigger<I> {
+    /// Creates a new analog trigger on an input.
+    /// # Panics
+    /// Panics if all analog triggers are in use. See [`AnalogTrigger::try_new`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn new(input: I) -> Self {
+        Self::try_new(input)
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize analog trigger: {e}"))
+    }
+
+    /// Creates a new analog trigger on an input.
+    /// # Errors
+    /// Returns [`AnalogError::NoAvailableTriggers`] if all analog triggers are in use.
+    /// The input is returned with the error.
+    pub fn try_new(input: I) -> Result<Self, (AnalogError, I)> {
+        match input.initialize_trigger() {
+            Ok(handle) => Ok(Self { handle, input }),
+            Err(AnalogError::NoAvailableResources) => {
+                Err((AnalogError::NoAvailableTriggers, input))
+            }
+            Err(e) => Err((e, input)),
+        }
+    }
+
+    /// Gets the input this trigger is on.
+    #[must_use]
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    /// Frees the analog trigger, returning its input.
+    #[must_use]
+    pub fn into_input(self) -> I {
+        let this = ManuallyDrop::new(self);
+        this.free();
+        // SAFETY: `this` is never dropped, so the input is moved out exactly once.
+        unsafe { std::ptr::read(&this.input) }
+    }
+
+    fn free(&self) {
+        crate::reactor::dio::release(self.handle);
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_CleanAnalogTrigger(self.handle, std::ptr::from_mut(&mut status));
+        }
+    }
+}
+
+impl<const N: u8> AnalogTrigger<AnalogInput<N>> {
+    /// Sets the limits of the trigger as voltages.
+    /// # Errors
+    /// Returns [`AnalogError::LimitOrder`] if `lower` is above `upper`.
+    pub fn set_limits_voltage(
+        &mut self,
+        lower: ElectricPotential,
+        upper: ElectricPotential,
+    ) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogTriggerLimitsVoltage(
+                self.handle,
+                lower.get::<volt>(),
+                upper.get::<volt>(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Sets the limits of the trigger as raw 12-bit ADC values.
+    /// # Errors
+    /// Returns [`AnalogError::LimitOrder`] if `lower` is above `upper`.
+    pub fn set_limits_raw(&mut self, lower: i32, upper: i32) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogTriggerLimitsRaw(
+                self.handle,
+                lower,
+                upper,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Compares the averaged value of the input against the limits, rather than the raw value.
+    /// This can't be combined with [`AnalogTrigger::set_filtered`].
+    /// # Errors
+    /// Returns an error if filtering is enabled.
+    pub fn set_averaged(&mut self, averaged: bool) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogTriggerAveraged(
+                self.handle,
+                i32::from(averaged),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+
+    /// Passes the input through a 3-point filter which rejects outliers before comparing it
+    /// against the limits. This is useful e.g. for a continuous potentiometer, which briefly
+    /// outputs garbage as it wraps around. This can't be combined with [`AnalogTrigger::set_averaged`].
+    /// # Errors
+    /// Returns an error if averaging is enabled.
+    pub fn set_filtered(&mut self, filtered: bool) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogTriggerFiltered(
+                self.handle,
+                i32::from(filtered),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+}
+
+impl<S: DigitalSource> AnalogTrigger<DutyCycleInput<S>> {
+    /// Sets the limits of the trigger as duty cycles from 0 to 1.
+    /// # Errors
+    /// Returns [`AnalogError::LimitOrder`] if `lower` is above `upper`, or
+    /// [`AnalogError::ParameterOutOfRange`] if either limit is outside 0 to 1.
+    pub fn set_limits_duty_cycle(&mut self, lower: f64, upper: f64) -> Result<(), AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAnalogTriggerLimitsDutyCycle(
+                self.handle,
+                lower,
+                upper,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        AnalogError::from_status(status)
+    }
+}
+
+impl<I: TriggerInput> AnalogTrigger<I> {
+    /// Checks whether the input is currently between the lower and upper limits.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the trigger.
+    pub fn in_window(&self) -> Result<bool, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let in_window = unsafe {
+            wpihal_sys::HAL_GetAnalogTriggerInWindow(self.handle, std::ptr::from_mut(&mut status))
+        };
+        AnalogError::from_status(status)?;
+        Ok(in_window != 0)
+    }
+
+    /// Gets the state of the trigger. See [`AnalogTrigger`] for how the state changes.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the trigger.
+    pub fn state(&self) -> Result<bool, AnalogError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let state = unsafe {
+            wpihal_sys::HAL_GetAnalogTriggerTriggerState(
+                self.handle,
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        AnalogError::from_status(status)?;
+        Ok(state != 0)
+    }
+
+    /// Returns a stream of the rising and falling edges of the trigger state,
+    /// timestamped by the FPGA.
+    /// # Errors
+    /// Returns [`AnalogError::NoAvailableInterrupts`] if all of the FPGA's interrupts are in use.
+    pub fn edges(&mut self) -> Result<EdgeStream<'_, AnalogError>, AnalogError> {
+        Ok(EdgeStream::new(self.source())?)
+    }
+
+    fn source(&self) -> Source {
+        Source::analog_trigger(
+            self.handle,
+            wpihal_sys::HAL_AnalogTriggerType_HAL_Trigger_kState,
+        )
+    }
+
+    async fn wait_for_state(&mut self, state: bool) -> Result<(), AnalogError> {
+        // start counting edges before reading the state, so an edge in between isn't missed
+        let edge = crate::reactor::dio::interrupt(self.source())?.edge(if state {
+            EdgeType::Rising
+        } else {
+            EdgeType::Falling
+        });
+        if self.state()? == state {
+            return Ok(());
+        }
+        edge.await;
+        Ok(())
+    }
+
+    async fn wait_for_edge(&mut self, edge: EdgeType) -> Result<(), AnalogError> {
+        crate::reactor::dio::wait_for_edge(self.source(), edge).await?;
+        Ok(())
+    }
+}
+
+impl<I: TriggerInput> ErrorType for AnalogTrigger<I> {
+    type Error = AnalogError;
+}
+
+impl<I: TriggerInput> crate::Sealed for AnalogTrigger<I> {}
+
+/// Routes the trigger state.
+impl<I: TriggerInput> DigitalSource for AnalogTrigger<I> {
+    fn source_handle(&self) -> wpihal_sys::HAL_Handle {
+        self.handle
+    }
+
+    fn trigger_type(&self) -> wpihal_sys::HAL_AnalogTriggerType {
+        wpihal_sys::HAL_AnalogTriggerType_HAL_Trigger_kState
+    }
+}
+
+impl<I: TriggerInput> InputPin for AnalogTrigger<I> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.state()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.state()?)
+    }
+}
+
+impl<I: TriggerInput> Wait for AnalogTrigger<I> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_state(true).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_state(false).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(EdgeType::Rising).await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(EdgeType::Falling).await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_edge(EdgeType::Either).await
+    }
+}
+
+impl<I: TriggerInput> Drop for AnalogTrigger<I> {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
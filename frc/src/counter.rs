@@ -0,0 +1,419 @@
+//! FPGA counters.
+//!
+//! The FPGA has 8 counters which count edges of digital signals, and measure the period
+//! between them. A [`Counter`] takes ownership of the [`DigitalSource`]s it counts, which are
+//! returned by [`Counter::into_sources`]. Each counting mode is a separate typestate:
+//! - [`UpDown`] counts up on edges of one source and down on edges of another (optional) source.
+//! - [`ExternalDirection`] counts edges of one source, with the direction set by another source.
+//! - [`SemiPeriod`] measures how long a source stays high (or low), e.g. for PWM-output sensors.
+//! - [`PulseLength`] counts up or down depending on the length of each pulse, as used by
+//!   direction-sensing gear tooth sensors.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{marker::PhantomData, mem::ManuallyDrop};
+
+use thiserror::Error;
+use uom::si::{f64::Time, time::second};
+
+use crate::dio::DigitalSource;
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum CounterError {
+    #[error("all counters are in use")]
+    NoAvailableCounters,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl CounterError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(CounterError::NoAvailableCounters),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(CounterError::ParameterOutOfRange),
+            a => Err(CounterError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+pub trait CounterMode: crate::Sealed {}
+
+/// Up/down counting mode (typestate)
+pub struct UpDown;
+
+/// External direction counting mode (typestate)
+pub struct ExternalDirection;
+
+/// Semi-period measurement mode (typestate)
+pub struct SemiPeriod;
+
+/// Pulse length counting mode (typestate)
+pub struct PulseLength;
+
+impl crate::Sealed for UpDown {}
+impl CounterMode for UpDown {}
+
+impl crate::Sealed for ExternalDirection {}
+impl CounterMode for ExternalDirection {}
+
+impl crate::Sealed for SemiPeriod {}
+impl CounterMode for SemiPeriod {}
+
+impl crate::Sealed for PulseLength {}
+impl CounterMode for PulseLength {}
+
+/// Placeholder for a counter without a second source.
+pub struct NoSource;
+
+/// An FPGA counter in the given mode, counting `Up` and optionally `Down`.
+pub struct Counter<MODE: CounterMode, Up: DigitalSource, Down = NoSource> {
+    handle: wpihal_sys::HAL_CounterHandle,
+    up: Up,
+    down: Down,
+    _mode: PhantomData<MODE>,
+}
+
+impl<Up: DigitalSource> Counter<UpDown, Up> {
+    /// Creates a counter which counts up on rising edges of `up`.
+    /// # Errors
+    /// Returns [`CounterError::NoAvailableCounters`] if all counters are in use.
+    /// The source is returned with the error.
+    pub fn up(up: Up) -> Result<Self, (CounterError, Up)> {
+        Self::new(
+            wpihal_sys::HAL_Counter_Mode_HAL_Counter_kTwoPulse,
+            up,
+            NoSource,
+        )
+        .map_err(|(e, (up, NoSource))| (e, up))
+    }
+}
+
+impl<Up: DigitalSource, Down: DigitalSource> Counter<UpDown, Up, Down> {
+    /// Creates a counter which counts up on rising edges of `up`, and down on
+    /// rising edges of `down`.
+    /// # Errors
+    /// Returns [`CounterError::NoAvailableCounters`] if all counters are in use.
+    /// The sources are returned with the error.
+    pub fn up_down(up: Up, down: Down) -> Result<Self, (CounterError, (Up, Down))> {
+        Self::new(wpihal_sys::HAL_Counter_Mode_HAL_Counter_kTwoPulse, up, down)?
+            .with_down_source()?
+            .configure(|handle, status| unsafe {
+                wpihal_sys::HAL_SetCounterUpDownMode(handle, status);
+            })
+    }
+
+    /// Sets which edges of the down source are counted.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_down_edges(&mut self, rising: bool, falling: bool) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetCounterDownSourceEdge(
+                self.handle,
+                i32::from(rising),
+                i32::from(falling),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        CounterError::from_status(status)
+    }
+}
+
+impl<Up: DigitalSource, Down> Counter<UpDown, Up, Down> {
+    /// Sets which edges of the up source are counted.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_up_edges(&mut self, rising: bool, falling: bool) -> Result<(), CounterError> {
+        set_up_edges(self.handle, rising, falling)
+    }
+}
+
+impl<Up: DigitalSource, Direction: DigitalSource> Counter<ExternalDirection, Up, Direction> {
+    /// Creates a counter which counts rising edges of `up`, counting down
+    /// instead of up while `direction` is high.
+    /// # Errors
+    /// Returns [`CounterError::NoAvailableCounters`] if all counters are in use.
+    /// The sources are returned with the error.
+    pub fn external_direction(
+        up: Up,
+        direction: Direction,
+    ) -> Result<Self, (CounterError, (Up, Direction))> {
+        Self::new(
+            wpihal_sys::HAL_Counter_Mode_HAL_Counter_kExternalDirection,
+            up,
+            direction,
+        )?
+        .with_down_source()?
+        .configure(|handle, status| unsafe {
+            wpihal_sys::HAL_SetCounterExternalDirectionMode(handle, status);
+        })
+    }
+
+    /// Sets which edges of the up source are counted.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_up_edges(&mut self, rising: bool, falling: bool) -> Result<(), CounterError> {
+        set_up_edges(self.handle, rising, falling)
+    }
+}
+
+impl<Source: DigitalSource> Counter<SemiPeriod, Source> {
+    /// Creates a counter which measures how long `source` stays high, or low if `high` is false.
+    /// The measurement is read with [`Counter::period`].
+    /// # Errors
+    /// Returns [`CounterError::NoAvailableCounters`] if all counters are in use.
+    /// The source is returned with the error.
+    pub fn semi_period(source: Source, high: bool) -> Result<Self, (CounterError, Source)> {
+        Self::new(
+            wpihal_sys::HAL_Counter_Mode_HAL_Counter_kSemiperiod,
+            source,
+            NoSource,
+        )
+        .and_then(|counter| {
+            counter.configure(|handle, status| unsafe {
+                wpihal_sys::HAL_SetCounterSemiPeriodMode(handle, i32::from(high), status);
+            })
+        })
+        .map_err(|(e, (source, NoSource))| (e, source))
+    }
+}
+
+impl<Source: DigitalSource> Counter<PulseLength, Source> {
+    /// Creates a counter which counts pulses of `source`, counting up for pulses shorter
+    /// than `threshold` and down for longer pulses.
+    /// # Errors
+    /// Returns [`CounterError::NoAvailableCounters`] if all counters are in use.
+    /// The source is returned with the error.
+    pub fn pulse_length(source: Source, threshold: Time) -> Result<Self, (CounterError, Source)> {
+        Self::new(
+            wpihal_sys::HAL_Counter_Mode_HAL_Counter_kPulseLength,
+            source,
+            NoSource,
+        )
+        .and_then(|counter| {
+            counter.configure(|handle, status| unsafe {
+                wpihal_sys::HAL_SetCounterPulseLengthMode(
+                    handle,
+                    threshold.get::<second>(),
+                    status,
+                );
+            })
+        })
+        .map_err(|(e, (source, NoSource))| (e, source))
+    }
+}
+
+fn set_up_edges(
+    handle: wpihal_sys::HAL_CounterHandle,
+    rising: bool,
+    falling: bool,
+) -> Result<(), CounterError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe {
+        wpihal_sys::HAL_SetCounterUpSourceEdge(
+            handle,
+            i32::from(rising),
+            i32::from(falling),
+            std::ptr::from_mut(&mut status),
+        );
+    }
+    CounterError::from_status(status)
+}
+
+impl<MODE: CounterMode, Up: DigitalSource, Down> Counter<MODE, Up, Down> {
+    /// Allocates a counter and routes the up source to it. If anything fails, the
+    /// counter is freed and the sources are returned with the error.
+    fn new(
+        mode: wpihal_sys::HAL_Counter_Mode,
+        up: Up,
+        down: Down,
+    ) -> Result<Self, (CounterError, (Up, Down))> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let mut index = 0;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeCounter(
+                mode,
+                std::ptr::from_mut(&mut index),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        if let Err(e) = CounterError::from_status(status) {
+            return Err((e, (up, down)));
+        }
+        let (source, trigger_type) = (up.source_handle(), up.trigger_type());
+        let counter = Self {
+            handle,
+            up,
+            down,
+            _mode: PhantomData,
+        };
+        counter.configure(|handle, status| unsafe {
+            wpihal_sys::HAL_SetCounterUpSource(handle, source, trigger_type, status);
+        })
+    }
+
+    /// Calls a wpihal function to configure the counter. If it fails, the counter is
+    /// freed and the sources are returned with the error.
+    fn configure(
+        self,
+        f: impl FnOnce(wpihal_sys::HAL_CounterHandle, *mut i32),
+    ) -> Result<Self, (CounterError, (Up, Down))> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        f(self.handle, std::ptr::from_mut(&mut status));
+        match CounterError::from_status(status) {
+            Ok(()) => Ok(self),
+            Err(e) => Err((e, self.into_sources())),
+        }
+    }
+
+    /// Gets the current count.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the counter.
+    pub fn count(&self) -> Result<i32, CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let count =
+            unsafe { wpihal_sys::HAL_GetCounter(self.handle, std::ptr::from_mut(&mut status)) };
+        CounterError::from_status(status)?;
+        Ok(count)
+    }
+
+    /// Resets the count to zero.
+    /// # Errors
+    /// Returns an error if wpihal fails to reset the counter.
+    pub fn reset(&mut self) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_ResetCounter(self.handle, std::ptr::from_mut(&mut status)) };
+        CounterError::from_status(status)
+    }
+
+    /// Gets the period between the most recent counted edges, averaged over the configured
+    /// number of samples. In [`SemiPeriod`] mode, this is the length of the most recent pulse.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the counter.
+    pub fn period(&self) -> Result<Time, CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let period = unsafe {
+            wpihal_sys::HAL_GetCounterPeriod(self.handle, std::ptr::from_mut(&mut status))
+        };
+        CounterError::from_status(status)?;
+        Ok(Time::new::<second>(period))
+    }
+
+    /// Sets the period after which the source is considered stopped. See [`Counter::stopped`].
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_max_period(&mut self, max_period: Time) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetCounterMaxPeriod(
+                self.handle,
+                max_period.get::<second>(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        CounterError::from_status(status)
+    }
+
+    /// Checks whether the time since the last counted edge is longer than the maximum period.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the counter.
+    pub fn stopped(&self) -> Result<bool, CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let stopped = unsafe {
+            wpihal_sys::HAL_GetCounterStopped(self.handle, std::ptr::from_mut(&mut status))
+        };
+        CounterError::from_status(status)?;
+        Ok(stopped != 0)
+    }
+
+    /// Gets the direction of the most recent count: true for up, false for down.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the counter.
+    pub fn direction(&self) -> Result<bool, CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let direction = unsafe {
+            wpihal_sys::HAL_GetCounterDirection(self.handle, std::ptr::from_mut(&mut status))
+        };
+        CounterError::from_status(status)?;
+        Ok(direction != 0)
+    }
+
+    /// Inverts the counting direction.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_reverse_direction(&mut self, reverse: bool) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetCounterReverseDirection(
+                self.handle,
+                i32::from(reverse),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        CounterError::from_status(status)
+    }
+
+    /// Sets the number of samples (1 to 127) that [`Counter::period`] is averaged over.
+    /// # Errors
+    /// Returns [`CounterError::ParameterOutOfRange`] if `samples` is out of range.
+    pub fn set_samples_to_average(&mut self, samples: u8) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetCounterSamplesToAverage(
+                self.handle,
+                samples.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        CounterError::from_status(status)
+    }
+
+    /// Sets whether the period keeps updating once the source has stopped. If disabled,
+    /// the period of a stopped source is reported as the last measured period.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the counter.
+    pub fn set_update_when_empty(&mut self, enabled: bool) -> Result<(), CounterError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetCounterUpdateWhenEmpty(
+                self.handle,
+                i32::from(enabled),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        CounterError::from_status(status)
+    }
+
+    /// Frees the counter, returning its sources.
+    #[must_use]
+    pub fn into_sources(self) -> (Up, Down) {
+        let this = ManuallyDrop::new(self);
+        this.free();
+        // SAFETY: `this` is never dropped, so the sources are moved out exactly once.
+        unsafe { (std::ptr::read(&this.up), std::ptr::read(&this.down)) }
+    }
+
+    fn free(&self) {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_FreeCounter(self.handle, std::ptr::from_mut(&mut status)) };
+    }
+}
+
+impl<MODE: CounterMode, Up: DigitalSource, Down: DigitalSource> Counter<MODE, Up, Down> {
+    fn with_down_source(self) -> Result<Self, (CounterError, (Up, Down))> {
+        let (source, trigger_type) = (self.down.source_handle(), self.down.trigger_type());
+        self.configure(|handle, status| unsafe {
+            wpihal_sys::HAL_SetCounterDownSource(handle, source, trigger_type, status);
+        })
+    }
+}
+
+impl<MODE: CounterMode, Up: DigitalSource, Down> Drop for Counter<MODE, Up, Down> {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
@@ -0,0 +1,229 @@
+#![allow(clippy::module_name_repetitions)]
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin, PinState};
+use embedded_hal_async::digital::Wait;
+
+use super::{
+    initialize, read, set_direction, wait_for_edge, wait_for_level, write, DigitalSource, DioError,
+};
+use crate::reactor::dio::{EdgeStream, EdgeType, Source};
+
+/// Initializes a pin on a runtime channel, checking the channel is valid first.
+fn initialize_checked(channel: u8, input: bool) -> Result<wpihal_sys::HAL_DigitalHandle, DioError> {
+    let valid = unsafe { wpihal_sys::HAL_CheckDIOChannel(channel.into()) };
+    if valid == 0 {
+        return Err(DioError::OutOfRange);
+    }
+    initialize(channel, input)
+}
+
+/// A digital input pin with its channel stored at runtime. Created by [`Dio::erase`](super::Dio::erase)
+/// or [`AnyInput::new`]. Useful to store several input pins homogeneously, e.g. in a slice or array.
+/// For a pin with its channel encoded in the type system, see [`Dio`](super::Dio).
+pub struct AnyInput {
+    pub(super) handle: wpihal_sys::HAL_DigitalHandle,
+    pub(super) channel: u8,
+}
+
+impl AnyInput {
+    /// Creates a new input pin on the given channel. It is not recommended to use this
+    /// function unless you can be sure that no other object representing this pin exists.
+    /// # Panics
+    /// Panics if `channel` is not a valid DIO channel, or if the channel is already in use.
+    /// See [`AnyInput::try_new`] for a non-panicking version.
+    #[must_use]
+    pub fn new(channel: u8) -> Self {
+        Self::try_new(channel).unwrap_or_else(|e| panic!("failed to initialize DIO {channel}: {e}"))
+    }
+
+    /// Creates a new input pin on the given channel. It is not recommended to use this
+    /// function unless you can be sure that no other object representing this pin exists.
+    /// # Errors
+    /// Returns [`DioError::OutOfRange`] if `channel` is not a valid DIO channel,
+    /// or [`DioError::ResourceAlreadyAllocated`] if the channel is already in use.
+    pub fn try_new(channel: u8) -> Result<Self, DioError> {
+        Ok(Self {
+            handle: initialize_checked(channel, true)?,
+            channel,
+        })
+    }
+
+    /// Gets the wpihal channel number of this pin.
+    #[must_use]
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Changes this pin to an output.
+    /// # Panics
+    /// Panics if wpihal fails to change the pin's direction.
+    /// See [`AnyInput::try_into_output`] for a non-panicking version.
+    #[must_use]
+    pub fn into_output(self) -> AnyOutput {
+        let channel = self.channel;
+        self.try_into_output()
+            .unwrap_or_else(|(e, _)| panic!("failed to make DIO {channel} an output: {e}"))
+    }
+
+    /// Changes this pin to an output.
+    /// # Errors
+    /// Returns an error if wpihal fails to change the pin's direction.
+    /// The pin is returned unchanged with the error.
+    pub fn try_into_output(self) -> Result<AnyOutput, (DioError, Self)> {
+        crate::reactor::dio::release(self.handle);
+        if let Err(e) = set_direction(self.handle, false) {
+            return Err((e, self));
+        }
+        let this = std::mem::ManuallyDrop::new(self);
+        Ok(AnyOutput {
+            handle: this.handle,
+            channel: this.channel,
+        })
+    }
+
+    /// Returns a stream of the rising and falling edges on this pin, timestamped by the FPGA.
+    /// # Errors
+    /// Returns [`DioError::NoAvailableInterrupts`] if all of the FPGA's interrupts are in use.
+    pub fn edges(&mut self) -> Result<EdgeStream<'_>, DioError> {
+        Ok(EdgeStream::new(Source::digital(self.handle))?)
+    }
+}
+
+impl ErrorType for AnyInput {
+    type Error = DioError;
+}
+
+impl crate::Sealed for AnyInput {}
+
+impl DigitalSource for AnyInput {
+    fn source_handle(&self) -> wpihal_sys::HAL_Handle {
+        self.handle
+    }
+}
+
+impl InputPin for AnyInput {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(read(self.handle)? == PinState::High)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(read(self.handle)? == PinState::Low)
+    }
+}
+
+impl Wait for AnyInput {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self.handle, PinState::High).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self.handle, PinState::Low).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self.handle, EdgeType::Rising).await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self.handle, EdgeType::Falling).await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self.handle, EdgeType::Either).await
+    }
+}
+
+impl Drop for AnyInput {
+    fn drop(&mut self) {
+        crate::reactor::dio::release(self.handle);
+        unsafe { wpihal_sys::HAL_FreeDIOPort(self.handle) }
+    }
+}
+
+/// A digital output pin with its channel stored at runtime. Created by [`Dio::erase`](super::Dio::erase)
+/// or [`AnyOutput::new`]. Useful to store several output pins homogeneously, e.g. in a slice or array.
+/// For a pin with its channel encoded in the type system, see [`Dio`](super::Dio).
+pub struct AnyOutput {
+    pub(super) handle: wpihal_sys::HAL_DigitalHandle,
+    pub(super) channel: u8,
+}
+
+impl AnyOutput {
+    /// Creates a new output pin on the given channel. It is not recommended to use this
+    /// function unless you can be sure that no other object representing this pin exists.
+    /// # Panics
+    /// Panics if `channel` is not a valid DIO channel, or if the channel is already in use.
+    /// See [`AnyOutput::try_new`] for a non-panicking version.
+    #[must_use]
+    pub fn new(channel: u8) -> Self {
+        Self::try_new(channel).unwrap_or_else(|e| panic!("failed to initialize DIO {channel}: {e}"))
+    }
+
+    /// Creates a new output pin on the given channel. It is not recommended to use this
+    /// function unless you can be sure that no other object representing this pin exists.
+    /// # Errors
+    /// Returns [`DioError::OutOfRange`] if `channel` is not a valid DIO channel,
+    /// or [`DioError::ResourceAlreadyAllocated`] if the channel is already in use.
+    pub fn try_new(channel: u8) -> Result<Self, DioError> {
+        Ok(Self {
+            handle: initialize_checked(channel, false)?,
+            channel,
+        })
+    }
+
+    /// Gets the wpihal channel number of this pin.
+    #[must_use]
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Changes this pin to an input.
+    /// # Panics
+    /// Panics if wpihal fails to change the pin's direction.
+    /// See [`AnyOutput::try_into_input`] for a non-panicking version.
+    #[must_use]
+    pub fn into_input(self) -> AnyInput {
+        let channel = self.channel;
+        self.try_into_input()
+            .unwrap_or_else(|(e, _)| panic!("failed to make DIO {channel} an input: {e}"))
+    }
+
+    /// Changes this pin to an input.
+    /// # Errors
+    /// Returns an error if wpihal fails to change the pin's direction.
+    /// The pin is returned unchanged with the error.
+    pub fn try_into_input(self) -> Result<AnyInput, (DioError, Self)> {
+        if let Err(e) = set_direction(self.handle, true) {
+            return Err((e, self));
+        }
+        let this = std::mem::ManuallyDrop::new(self);
+        Ok(AnyInput {
+            handle: this.handle,
+            channel: this.channel,
+        })
+    }
+}
+
+impl ErrorType for AnyOutput {
+    type Error = DioError;
+}
+
+impl OutputPin for AnyOutput {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        write(self.handle, PinState::High)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        write(self.handle, PinState::Low)
+    }
+
+    fn set_state(&mut self, state: PinState) -> Result<(), Self::Error> {
+        write(self.handle, state)
+    }
+}
+
+impl Drop for AnyOutput {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeDIOPort(self.handle) }
+    }
+}
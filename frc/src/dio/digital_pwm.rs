@@ -0,0 +1,166 @@
+//! Digital PWM generators.
+//!
+//! The FPGA has six PWM generators which can each be routed to a DIO output pin.
+//! All generators share a single rate, which is owned by [`DigitalPwmRate`].
+//! The duty cycle of each generator is set through its [`DigitalPwm`].
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embedded_hal::pwm::{ErrorType, SetDutyCycle};
+use uom::si::{f64::Frequency, frequency::hertz};
+
+use super::{Dio, DioError, Output};
+
+/// The rate shared by all digital PWM generators.
+pub struct DigitalPwmRate {
+    _private: (),
+}
+
+static RATE_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl DigitalPwmRate {
+    pub fn take() -> Option<Self> {
+        let previously_taken = RATE_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self { _private: () })
+        }
+    }
+
+    /// Sets the frequency of all digital PWM outputs. The valid range is from
+    /// 0.6 Hz to 19 kHz, and the frequency resolution is logarithmic.
+    ///
+    /// At frequencies above roughly 5.5 kHz, wpihal scales duty cycles based on the
+    /// rate at the time they are set, so the rate should be set before duty cycles.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the rate.
+    pub fn set(&mut self, rate: Frequency) -> Result<(), DioError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetDigitalPWMRate(rate.get::<hertz>(), std::ptr::from_mut(&mut status));
+        }
+        DioError::from_status(status)
+    }
+}
+
+/// A digital PWM generator outputting on a DIO pin. Created by [`Dio::into_pwm`].
+pub struct DigitalPwm<const N: u8> {
+    pin: Dio<N, Output>,
+    generator: wpihal_sys::HAL_DigitalPWMHandle,
+}
+
+impl<const N: u8> Dio<N, Output> {
+    /// Allocates a digital PWM generator and routes it to this pin.
+    /// The pin is returned by [`DigitalPwm::into_output`].
+    /// # Errors
+    /// Returns [`DioError::NoAvailablePwmGenerators`] if all six generators are in use.
+    /// The pin is returned with the error.
+    pub fn into_pwm(self) -> Result<DigitalPwm<N>, (DioError, Self)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let generator =
+            unsafe { wpihal_sys::HAL_AllocateDigitalPWM(std::ptr::from_mut(&mut status)) };
+        if status == wpihal_sys::NO_AVAILABLE_RESOURCES {
+            return Err((DioError::NoAvailablePwmGenerators, self));
+        }
+        if let Err(e) = DioError::from_status(status) {
+            return Err((e, self));
+        }
+        let pwm = DigitalPwm {
+            pin: self,
+            generator,
+        };
+        unsafe {
+            wpihal_sys::HAL_SetDigitalPWMOutputChannel(
+                generator,
+                N.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        match DioError::from_status(status) {
+            Ok(()) => Ok(pwm),
+            Err(e) => Err((e, pwm.into_output())),
+        }
+    }
+}
+
+impl<const N: u8> DigitalPwm<N> {
+    /// Sets the duty cycle as a fraction from 0 to 1.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the duty cycle.
+    pub fn set_duty_cycle_f64(&mut self, duty_cycle: f64) -> Result<(), DioError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetDigitalPWMDutyCycle(
+                self.generator,
+                duty_cycle,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        DioError::from_status(status)
+    }
+
+    /// Switches all digital PWM generators to output a pulse-per-second signal, and
+    /// sets the duty cycle of this generator as a fraction from 0 to 1. As this changes
+    /// the shared rate, it requires the [`DigitalPwmRate`].
+    /// # Errors
+    /// Returns an error if wpihal fails to set the duty cycle.
+    pub fn set_pps(&mut self, _rate: &mut DigitalPwmRate, duty_cycle: f64) -> Result<(), DioError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetDigitalPWMPPS(
+                self.generator,
+                duty_cycle,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        DioError::from_status(status)
+    }
+
+    /// Frees the PWM generator, releasing the output pin.
+    #[must_use]
+    pub fn into_output(self) -> Dio<N, Output> {
+        let this = ManuallyDrop::new(self);
+        this.free_generator();
+        // SAFETY: `this` is never dropped, so the pin is moved out exactly once.
+        unsafe { std::ptr::read(&this.pin) }
+    }
+
+    fn free_generator(&self) {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            // route the generator to a nonexistent channel to stop its output before freeing it
+            wpihal_sys::HAL_SetDigitalPWMOutputChannel(
+                self.generator,
+                wpihal_sys::HAL_GetNumDigitalChannels(),
+                std::ptr::from_mut(&mut status),
+            );
+            wpihal_sys::HAL_FreeDigitalPWM(self.generator, std::ptr::from_mut(&mut status));
+        }
+    }
+}
+
+impl<const N: u8> ErrorType for DigitalPwm<N> {
+    type Error = DioError;
+}
+
+impl<const N: u8> SetDutyCycle for DigitalPwm<N> {
+    fn max_duty_cycle(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        self.set_duty_cycle_f64(f64::from(duty) / f64::from(u16::MAX))
+    }
+}
+
+impl<const N: u8> Drop for DigitalPwm<N> {
+    fn drop(&mut self) {
+        self.free_generator();
+    }
+}
@@ -0,0 +1,179 @@
+//! Digital glitch filters.
+//!
+//! The FPGA has three glitch filters shared between all DIO pins. Each filter has a
+//! configurable period, and pins attached to a filter ignore any pulse shorter than
+//! that period. Any number of pins can be attached to the same filter.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embedded_hal::digital::{ErrorType, InputPin};
+use embedded_hal_async::digital::Wait;
+use uom::si::{f64::Time, time::microsecond};
+
+use super::{DigitalSource, Dio, DioError, Input};
+
+/// Number of filter clock cycles per microsecond. The filters run at a quarter
+/// of the FPGA system clock.
+fn cycles_per_microsecond() -> f64 {
+    f64::from(unsafe { wpihal_sys::HAL_GetSystemClockTicksPerMicrosecond() }) / 4.0
+}
+
+/// One of the FPGA's three digital glitch filters.
+pub struct GlitchFilter {
+    index: i32,
+}
+
+impl GlitchFilter {
+    /// Sets the period of the filter. Pulses shorter than this period on attached
+    /// pins are filtered out. This affects all pins attached to this filter.
+    /// # Errors
+    /// Returns [`DioError::ParameterOutOfRange`] if the period is negative or not finite,
+    /// or an error if wpihal fails to configure the filter.
+    pub fn set_period(&mut self, period: Time) -> Result<(), DioError> {
+        let microseconds = period.get::<microsecond>();
+        // written so that NaN is rejected too
+        if !(microseconds >= 0.0 && microseconds.is_finite()) {
+            return Err(DioError::ParameterOutOfRange);
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let cycles = (microseconds * cycles_per_microsecond()).round() as i64;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetFilterPeriod(self.index, cycles, std::ptr::from_mut(&mut status));
+        }
+        DioError::from_status(status)
+    }
+
+    /// Gets the currently configured period of the filter.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the filter.
+    pub fn period(&self) -> Result<Time, DioError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let cycles =
+            unsafe { wpihal_sys::HAL_GetFilterPeriod(self.index, std::ptr::from_mut(&mut status)) };
+        DioError::from_status(status)?;
+        #[allow(clippy::cast_precision_loss)]
+        Ok(Time::new::<microsecond>(
+            cycles as f64 / cycles_per_microsecond(),
+        ))
+    }
+
+    /// Attaches this filter to an input pin. The filter is detached from the pin
+    /// when the returned [`FilteredInput`] is dropped or released with
+    /// [`FilteredInput::into_inner`].
+    /// # Errors
+    /// Returns an error if wpihal fails to attach the filter. The pin is returned
+    /// with the error.
+    pub fn attach<const N: u8>(
+        &self,
+        pin: Dio<N, Input>,
+    ) -> Result<FilteredInput<N>, (DioError, Dio<N, Input>)> {
+        // wpihal filter selections are 1-indexed, 0 selects no filter
+        match select_filter(pin.handle, self.index + 1) {
+            Ok(()) => Ok(FilteredInput { pin }),
+            Err(e) => Err((e, pin)),
+        }
+    }
+}
+
+fn select_filter(handle: wpihal_sys::HAL_DigitalHandle, selection: i32) -> Result<(), DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe { wpihal_sys::HAL_SetFilterSelect(handle, selection, std::ptr::from_mut(&mut status)) };
+    DioError::from_status(status)
+}
+
+/// The FPGA's three digital glitch filters.
+pub struct GlitchFilters {
+    pub filter0: GlitchFilter,
+    pub filter1: GlitchFilter,
+    pub filter2: GlitchFilter,
+}
+
+static FILTERS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl GlitchFilters {
+    pub fn take() -> Option<Self> {
+        let previously_taken = FILTERS_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                filter0: GlitchFilter { index: 0 },
+                filter1: GlitchFilter { index: 1 },
+                filter2: GlitchFilter { index: 2 },
+            })
+        }
+    }
+}
+
+/// An input pin with a glitch filter attached. Created by [`GlitchFilter::attach`].
+pub struct FilteredInput<const N: u8> {
+    pin: Dio<N, Input>,
+}
+
+impl<const N: u8> FilteredInput<N> {
+    /// Detaches the glitch filter, releasing the input pin.
+    #[must_use]
+    pub fn into_inner(self) -> Dio<N, Input> {
+        // selecting no filter only fails for an invalid handle, which an owned pin can't have
+        let _ = select_filter(self.pin.handle, 0);
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so the pin is moved out exactly once.
+        unsafe { std::ptr::read(&this.pin) }
+    }
+}
+
+impl<const N: u8> ErrorType for FilteredInput<N> {
+    type Error = DioError;
+}
+
+impl<const N: u8> crate::Sealed for FilteredInput<N> {}
+
+impl<const N: u8> DigitalSource for FilteredInput<N> {
+    fn source_handle(&self) -> wpihal_sys::HAL_Handle {
+        self.pin.handle
+    }
+}
+
+impl<const N: u8> InputPin for FilteredInput<N> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+impl<const N: u8> Wait for FilteredInput<N> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.wait_for_high().await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.wait_for_low().await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.pin.wait_for_rising_edge().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.pin.wait_for_falling_edge().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.pin.wait_for_any_edge().await
+    }
+}
+
+impl<const N: u8> Drop for FilteredInput<N> {
+    fn drop(&mut self) {
+        let _ = select_filter(self.pin.handle, 0);
+    }
+}
@@ -23,16 +23,31 @@
 // ni's chipobject doesn't use this layout - it uses a c++ bitfield
 // wpihal has functions to convert a channel to a bit in ni's bitfield
 
+pub mod any;
+pub use any::*;
+pub mod digital_pwm;
+pub use digital_pwm::*;
+pub mod filter;
+pub use filter::*;
+pub mod pulse;
+pub use pulse::*;
+pub mod mxp;
+pub use mxp::*;
+pub mod spi;
+pub use spi::*;
+
 use std::{
     marker::PhantomData,
+    mem::ManuallyDrop,
     sync::atomic::{AtomicBool, Ordering},
 };
 
-use embedded_hal::digital::{ErrorType, InputPin, OutputPin, PinState};
+use embedded_hal::digital::{ErrorKind, ErrorType, InputPin, OutputPin, PinState};
 use embedded_hal_async::digital::Wait;
-use wpihal_sys::panic_on_hal_error;
+use thiserror::Error;
 
-use crate::reactor::dio::EdgeType;
+use crate::error::HalError;
+use crate::reactor::dio::{EdgeStream, EdgeType, InterruptError, Source};
 
 pub trait PinMode: crate::Sealed {}
 
@@ -45,6 +60,18 @@ impl PinMode for Output {}
 impl crate::Sealed for Uninitialized {}
 impl PinMode for Uninitialized {}
 
+/// A digital signal which can be routed to FPGA peripherals such as counters:
+/// an input pin, or the state of an analog trigger.
+pub trait DigitalSource: crate::Sealed {
+    /// The wpihal handle of the signal's source.
+    fn source_handle(&self) -> wpihal_sys::HAL_Handle;
+
+    /// Which output of an analog trigger is routed. This is ignored for input pins.
+    fn trigger_type(&self) -> wpihal_sys::HAL_AnalogTriggerType {
+        wpihal_sys::HAL_AnalogTriggerType_HAL_Trigger_kInWindow
+    }
+}
+
 /// Input pin mode (typestate)
 pub struct Input;
 
@@ -54,191 +81,343 @@ pub struct Output;
 /// Uninitialized pin mode (typestate)
 pub struct Uninitialized;
 
-/// Represents a DIO pin on the built-in port
-/// N is the pin number from 0..=9
+#[derive(Error, Debug)]
+pub enum DioError {
+    #[error("pin is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("channel is out of range")]
+    OutOfRange,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error("all interrupts are in use")]
+    NoAvailableInterrupts,
+    #[error("all digital PWM generators are in use")]
+    NoAvailablePwmGenerators,
+    #[error("no resources are available")]
+    NoAvailableResources,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl DioError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(DioError::ResourceAlreadyAllocated),
+            wpihal_sys::RESOURCE_OUT_OF_RANGE => Err(DioError::OutOfRange),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(DioError::ParameterOutOfRange),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(DioError::NoAvailableResources),
+            a => Err(DioError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+impl From<InterruptError> for DioError {
+    fn from(value: InterruptError) -> Self {
+        match value {
+            InterruptError::NoAvailableInterrupt => DioError::NoAvailableInterrupts,
+            InterruptError::BadHandle => DioError::Hal(HalError::new(wpihal_sys::HAL_HANDLE_ERROR)),
+            InterruptError::Fpga(e) => DioError::Hal(e),
+        }
+    }
+}
+
+impl embedded_hal::digital::Error for DioError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl embedded_hal::pwm::Error for DioError {
+    fn kind(&self) -> embedded_hal::pwm::ErrorKind {
+        embedded_hal::pwm::ErrorKind::Other
+    }
+}
+
+fn initialize(channel: u8, input: bool) -> Result<wpihal_sys::HAL_DigitalHandle, DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let handle = unsafe {
+        wpihal_sys::HAL_InitializeDIOPort(
+            wpihal_sys::HAL_GetPort(channel.into()),
+            i32::from(input),
+            c"".as_ptr(),
+            std::ptr::from_mut(&mut status),
+        )
+    };
+    DioError::from_status(status)?;
+    Ok(handle)
+}
+
+fn set_direction(handle: wpihal_sys::HAL_DigitalHandle, input: bool) -> Result<(), DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe {
+        wpihal_sys::HAL_SetDIODirection(handle, i32::from(input), std::ptr::from_mut(&mut status));
+    }
+    DioError::from_status(status)
+}
+
+fn read(handle: wpihal_sys::HAL_DigitalHandle) -> Result<PinState, DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let val = unsafe { wpihal_sys::HAL_GetDIO(handle, std::ptr::from_mut(&mut status)) };
+    DioError::from_status(status)?;
+    Ok(PinState::from(val != 0))
+}
+
+fn write(handle: wpihal_sys::HAL_DigitalHandle, state: PinState) -> Result<(), DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe {
+        wpihal_sys::HAL_SetDIO(
+            handle,
+            i32::from(bool::from(state)),
+            std::ptr::from_mut(&mut status),
+        );
+    }
+    DioError::from_status(status)
+}
+
+async fn wait_for_edge(
+    handle: wpihal_sys::HAL_DigitalHandle,
+    edge: EdgeType,
+) -> Result<(), DioError> {
+    crate::reactor::dio::wait_for_edge(Source::digital(handle), edge).await?;
+    Ok(())
+}
+
+async fn wait_for_level(
+    handle: wpihal_sys::HAL_DigitalHandle,
+    state: PinState,
+) -> Result<(), DioError> {
+    // start counting edges before reading the pin, so an edge in between isn't missed
+    let edge = crate::reactor::dio::interrupt(Source::digital(handle))?.edge(match state {
+        PinState::High => EdgeType::Rising,
+        PinState::Low => EdgeType::Falling,
+    });
+    if read(handle)? == state {
+        return Ok(());
+    }
+    edge.await;
+    Ok(())
+}
+
+/// Represents a DIO pin.
+/// N is the wpihal channel number: 0..=9 for the built-in port, 10..=25 for the MXP
+/// (see [`MxpPort`]), or 26..=30 for the SPI port (see [`SpiDioPort`]).
 pub struct Dio<const N: u8, MODE: PinMode = Uninitialized> {
     // sigh... no ZST for me
     handle: wpihal_sys::HAL_DigitalHandle,
     _mode: PhantomData<MODE>,
 }
 
+impl<const N: u8, MODE: PinMode> Dio<N, MODE> {
+    /// Takes the wpihal handle out of this pin without freeing it.
+    fn into_handle(self) -> wpihal_sys::HAL_DigitalHandle {
+        ManuallyDrop::new(self).handle
+    }
+
+    fn from_handle<NEW: PinMode>(handle: wpihal_sys::HAL_DigitalHandle) -> Dio<N, NEW> {
+        Dio {
+            handle,
+            _mode: PhantomData,
+        }
+    }
+}
+
 impl<const N: u8, MODE: PinMode> ErrorType for Dio<N, MODE> {
-    type Error = std::convert::Infallible;
+    type Error = DioError;
 }
 
 impl<const N: u8> Dio<N, Uninitialized> {
-    /// assert that N is in 0..=9
-    const _VALID: () = assert!(N < 10);
+    /// assert that N is in 0..=30
+    const _VALID: () = assert!(N < 31);
 
     /// Creates a new digital pin. It is not recommended to use this function
     /// unless you can be sure that no other object representing this pin exists.
     /// Violating this will not result in undefined behaviour, but can lead to
     /// races if both objects attempt to change the pin state.
     pub const fn new_uninit() -> Self {
+        let () = Self::_VALID;
         Self {
             handle: 0,
             _mode: PhantomData,
         }
     }
 
+    /// Initializes this pin as an input.
+    /// # Panics
+    /// Panics if wpihal fails to initialize the pin, e.g. if it is already allocated.
+    /// See [`Dio::try_into_input`] for a non-panicking version.
     pub fn into_input(self) -> Dio<N, Input> {
-        let handle = unsafe {
-            panic_on_hal_error(|status| {
-                wpihal_sys::HAL_InitializeDIOPort(
-                    wpihal_sys::HAL_GetPort(N.into()),
-                    1,
-                    c"".as_ptr(),
-                    status,
-                )
-            })
-        };
-        Dio {
-            handle,
-            _mode: PhantomData,
-        }
+        self.try_into_input()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize DIO {N}: {e}"))
     }
 
+    /// Initializes this pin as an output.
+    /// # Panics
+    /// Panics if wpihal fails to initialize the pin, e.g. if it is already allocated.
+    /// See [`Dio::try_into_output`] for a non-panicking version.
     pub fn into_output(self) -> Dio<N, Output> {
-        let handle = unsafe {
-            panic_on_hal_error(|status| {
-                wpihal_sys::HAL_InitializeDIOPort(
-                    wpihal_sys::HAL_GetPort(N.into()),
-                    0,
-                    c"".as_ptr(),
-                    status,
-                )
-            })
-        };
-        Dio {
-            handle,
-            _mode: PhantomData,
+        self.try_into_output()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize DIO {N}: {e}"))
+    }
+
+    /// Initializes this pin as an input.
+    /// # Errors
+    /// Returns an error if wpihal fails to initialize the pin, e.g. with
+    /// [`DioError::ResourceAlreadyAllocated`] if the pin is in use elsewhere.
+    /// The pin is returned with the error.
+    pub fn try_into_input(self) -> Result<Dio<N, Input>, (DioError, Self)> {
+        match initialize(N, true) {
+            Ok(handle) => Ok(Self::from_handle(handle)),
+            Err(e) => Err((e, self)),
+        }
+    }
+
+    /// Initializes this pin as an output.
+    /// # Errors
+    /// Returns an error if wpihal fails to initialize the pin, e.g. with
+    /// [`DioError::ResourceAlreadyAllocated`] if the pin is in use elsewhere.
+    /// The pin is returned with the error.
+    pub fn try_into_output(self) -> Result<Dio<N, Output>, (DioError, Self)> {
+        match initialize(N, false) {
+            Ok(handle) => Ok(Self::from_handle(handle)),
+            Err(e) => Err((e, self)),
         }
     }
 }
 
 impl<const N: u8> Dio<N, Input> {
-    fn read(&self) -> PinState {
-        let val =
-            unsafe { panic_on_hal_error(|status| wpihal_sys::HAL_GetDIO(self.handle, status)) };
-        PinState::from(val != 0)
+    /// Changes this pin to an output.
+    /// # Panics
+    /// Panics if wpihal fails to change the pin's direction.
+    /// See [`Dio::try_into_output`] for a non-panicking version.
+    pub fn into_output(self) -> Dio<N, Output> {
+        self.try_into_output()
+            .unwrap_or_else(|(e, _)| panic!("failed to make DIO {N} an output: {e}"))
     }
 
-    pub fn into_output(self) -> Dio<N, Output> {
-        unsafe {
-            panic_on_hal_error(|status| {
-                wpihal_sys::HAL_SetDIODirection(self.handle, 0, status);
-            });
+    /// Changes this pin to an output.
+    /// # Errors
+    /// Returns an error if wpihal fails to change the pin's direction.
+    /// The pin is returned unchanged with the error.
+    pub fn try_into_output(self) -> Result<Dio<N, Output>, (DioError, Self)> {
+        crate::reactor::dio::release(self.handle);
+        match set_direction(self.handle, false) {
+            Ok(()) => Ok(Self::from_handle(self.into_handle())),
+            Err(e) => Err((e, self)),
         }
-        Dio {
-            handle: self.handle,
-            _mode: PhantomData,
+    }
+
+    /// Type-erases the channel of this pin, storing it at runtime.
+    #[must_use]
+    pub fn erase(self) -> AnyInput {
+        AnyInput {
+            handle: self.into_handle(),
+            channel: N,
         }
     }
+
+    /// Returns a stream of the rising and falling edges on this pin, timestamped by the FPGA.
+    /// # Errors
+    /// Returns [`DioError::NoAvailableInterrupts`] if all of the FPGA's interrupts are in use.
+    pub fn edges(&mut self) -> Result<EdgeStream<'_>, DioError> {
+        Ok(EdgeStream::new(Source::digital(self.handle))?)
+    }
+}
+
+impl<const N: u8> crate::Sealed for Dio<N, Input> {}
+
+impl<const N: u8> DigitalSource for Dio<N, Input> {
+    fn source_handle(&self) -> wpihal_sys::HAL_Handle {
+        self.handle
+    }
 }
 
 impl<const N: u8> InputPin for Dio<N, Input> {
     fn is_high(&mut self) -> Result<bool, Self::Error> {
-        Ok(self.read() == PinState::High)
+        Ok(read(self.handle)? == PinState::High)
     }
 
     fn is_low(&mut self) -> Result<bool, Self::Error> {
-        Ok(self.read() == PinState::Low)
+        Ok(read(self.handle)? == PinState::Low)
     }
 }
 
 impl<const N: u8> Dio<N, Output> {
-    fn write(&mut self, state: PinState) {
-        unsafe {
-            panic_on_hal_error(|status| {
-                wpihal_sys::HAL_SetDIO(self.handle, i32::from(bool::from(state)), status);
-            });
-        }
-    }
-
     pub fn into_uninit(self) -> Dio<N, Uninitialized> {
-        unsafe { wpihal_sys::HAL_FreeDIOPort(self.handle) };
-        Dio {
-            handle: 0,
-            _mode: PhantomData,
-        }
+        unsafe { wpihal_sys::HAL_FreeDIOPort(self.into_handle()) };
+        Self::from_handle(0)
     }
 
+    /// Changes this pin to an input.
+    /// # Panics
+    /// Panics if wpihal fails to change the pin's direction.
+    /// See [`Dio::try_into_input`] for a non-panicking version.
     pub fn into_input(self) -> Dio<N, Input> {
-        unsafe {
-            panic_on_hal_error(|status| {
-                wpihal_sys::HAL_SetDIODirection(self.handle, 1, status);
-            });
+        self.try_into_input()
+            .unwrap_or_else(|(e, _)| panic!("failed to make DIO {N} an input: {e}"))
+    }
+
+    /// Changes this pin to an input.
+    /// # Errors
+    /// Returns an error if wpihal fails to change the pin's direction.
+    /// The pin is returned unchanged with the error.
+    pub fn try_into_input(self) -> Result<Dio<N, Input>, (DioError, Self)> {
+        match set_direction(self.handle, true) {
+            Ok(()) => Ok(Self::from_handle(self.into_handle())),
+            Err(e) => Err((e, self)),
         }
-        Dio {
-            handle: self.handle,
-            _mode: PhantomData,
+    }
+
+    /// Type-erases the channel of this pin, storing it at runtime.
+    #[must_use]
+    pub fn erase(self) -> AnyOutput {
+        AnyOutput {
+            handle: self.into_handle(),
+            channel: N,
         }
     }
 }
 
 impl<const N: u8> OutputPin for Dio<N, Output> {
     fn set_high(&mut self) -> Result<(), Self::Error> {
-        self.write(PinState::High);
-        Ok(())
+        write(self.handle, PinState::High)
     }
 
     fn set_low(&mut self) -> Result<(), Self::Error> {
-        self.write(PinState::Low);
-        Ok(())
+        write(self.handle, PinState::Low)
     }
 
     fn set_state(&mut self, state: PinState) -> Result<(), Self::Error> {
-        self.write(state);
-        Ok(())
+        write(self.handle, state)
     }
 }
 
 impl<const N: u8> Wait for Dio<N, Input> {
     async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
-        if self.is_high().unwrap() {
-            return Ok(());
-        }
-        self.wait_for_rising_edge().await
+        wait_for_level(self.handle, PinState::High).await
     }
 
     async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
-        if self.is_low().unwrap() {
-            return Ok(());
-        }
-        self.wait_for_falling_edge().await
+        wait_for_level(self.handle, PinState::Low).await
     }
 
     async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
-        crate::reactor::dio::wait_for_edge(self.handle, EdgeType::Rising)
-            .await
-            .unwrap_or_else(|e| {
-                eprintln!("error waiting for pin {N}: {e}");
-            });
-        Ok(())
+        wait_for_edge(self.handle, EdgeType::Rising).await
     }
 
     async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
-        crate::reactor::dio::wait_for_edge(self.handle, EdgeType::Falling)
-            .await
-            .unwrap_or_else(|e| {
-                eprintln!("error waiting for pin {N}: {e}");
-            });
-        Ok(())
+        wait_for_edge(self.handle, EdgeType::Falling).await
     }
 
     async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
-        crate::reactor::dio::wait_for_edge(self.handle, EdgeType::Either)
-            .await
-            .unwrap_or_else(|e| {
-                eprintln!("error waiting for pin {N}: {e}");
-            });
-        Ok(())
+        wait_for_edge(self.handle, EdgeType::Either).await
     }
 }
 
 impl<const N: u8, MODE: PinMode> Drop for Dio<N, MODE> {
     fn drop(&mut self) {
         if self.handle != 0 {
+            crate::reactor::dio::release(self.handle);
             unsafe { wpihal_sys::HAL_FreeDIOPort(self.handle) }
         }
     }
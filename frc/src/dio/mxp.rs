@@ -0,0 +1,132 @@
+//! Pins on the MXP (myRIO Expansion Port).
+//!
+//! Every MXP pin can be used as a DIO pin, but most also have an alternate function
+//! (PWM, SPI or I2C). A pin can only serve one function at a time, so the alternate
+//! functions consume the same uninitialized [`Dio`] that would otherwise be used for DIO.
+//! The traits in this module mark which pins support which alternate function.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{Dio, Uninitialized};
+
+/// An MXP pin which can be used as a PWM output instead of a DIO pin.
+pub trait MxpPwmPin: crate::Sealed {
+    /// The wpihal PWM channel routed to this pin. MXP PWM 0..=9 are wpihal
+    /// PWM channels 10..=19.
+    const PWM_CHANNEL: u8;
+}
+
+/// An MXP pin which can be used as part of the MXP SPI port instead of a DIO pin.
+pub trait MxpSpiPin: crate::Sealed {}
+
+/// An MXP pin which can be used as part of the MXP I2C port instead of a DIO pin.
+pub trait MxpI2cPin: crate::Sealed {}
+
+macro_rules! mxp_pin {
+    ($name:ident, $num:expr) => {
+        pub type $name = Dio<$num>;
+
+        impl crate::Sealed for Dio<$num, Uninitialized> {}
+    };
+    ($name:ident, $num:expr, pwm $pwm:expr) => {
+        mxp_pin!($name, $num);
+
+        impl MxpPwmPin for Dio<$num, Uninitialized> {
+            const PWM_CHANNEL: u8 = $pwm + 10;
+        }
+    };
+    ($name:ident, $num:expr, spi) => {
+        mxp_pin!($name, $num);
+
+        impl MxpSpiPin for Dio<$num, Uninitialized> {}
+    };
+    ($name:ident, $num:expr, i2c) => {
+        mxp_pin!($name, $num);
+
+        impl MxpI2cPin for Dio<$num, Uninitialized> {}
+    };
+}
+
+mxp_pin!(Dio10, 10, pwm 0);
+mxp_pin!(Dio11, 11, pwm 1);
+mxp_pin!(Dio12, 12, pwm 2);
+mxp_pin!(Dio13, 13, pwm 3);
+mxp_pin!(Dio14, 14, spi);
+mxp_pin!(Dio15, 15, spi);
+mxp_pin!(Dio16, 16, spi);
+mxp_pin!(Dio17, 17, spi);
+mxp_pin!(Dio18, 18, pwm 4);
+mxp_pin!(Dio19, 19, pwm 5);
+mxp_pin!(Dio20, 20, pwm 6);
+mxp_pin!(Dio21, 21, pwm 7);
+mxp_pin!(Dio22, 22, pwm 8);
+mxp_pin!(Dio23, 23, pwm 9);
+mxp_pin!(Dio24, 24, i2c);
+mxp_pin!(Dio25, 25, i2c);
+
+/// The DIO channels of the MXP. Each pin is handed out uninitialized, and can be
+/// turned into a DIO pin or given to its alternate function.
+pub struct MxpPort {
+    /// MXP PWM 0
+    pub dio10: Dio10,
+    /// MXP PWM 1
+    pub dio11: Dio11,
+    /// MXP PWM 2
+    pub dio12: Dio12,
+    /// MXP PWM 3
+    pub dio13: Dio13,
+    /// MXP SPI CS
+    pub dio14: Dio14,
+    /// MXP SPI CLK
+    pub dio15: Dio15,
+    /// MXP SPI MISO
+    pub dio16: Dio16,
+    /// MXP SPI MOSI
+    pub dio17: Dio17,
+    /// MXP PWM 4
+    pub dio18: Dio18,
+    /// MXP PWM 5
+    pub dio19: Dio19,
+    /// MXP PWM 6
+    pub dio20: Dio20,
+    /// MXP PWM 7
+    pub dio21: Dio21,
+    /// MXP PWM 8
+    pub dio22: Dio22,
+    /// MXP PWM 9
+    pub dio23: Dio23,
+    /// MXP I2C SCL
+    pub dio24: Dio24,
+    /// MXP I2C SDA
+    pub dio25: Dio25,
+}
+
+static MXP_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl MxpPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = MXP_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                dio10: Dio10::new_uninit(),
+                dio11: Dio11::new_uninit(),
+                dio12: Dio12::new_uninit(),
+                dio13: Dio13::new_uninit(),
+                dio14: Dio14::new_uninit(),
+                dio15: Dio15::new_uninit(),
+                dio16: Dio16::new_uninit(),
+                dio17: Dio17::new_uninit(),
+                dio18: Dio18::new_uninit(),
+                dio19: Dio19::new_uninit(),
+                dio20: Dio20::new_uninit(),
+                dio21: Dio21::new_uninit(),
+                dio22: Dio22::new_uninit(),
+                dio23: Dio23::new_uninit(),
+                dio24: Dio24::new_uninit(),
+                dio25: Dio25::new_uninit(),
+            })
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Hardware pulse generation on output pins.
+//!
+//! The FPGA can output a single pulse of 1 to 65535 microseconds on one or several
+//! output pins. The pulse length register is shared by all pins, so only one pulse
+//! can be generated at a time - concurrent pulses wait for each other to finish.
+
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use uom::si::{
+    f64::Time,
+    time::{microsecond, second},
+};
+
+use super::{AnyOutput, Dio, DioError, Output};
+
+static PULSE_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Checks that a pulse length can be generated by the FPGA, returning it in seconds.
+fn pulse_length_seconds(length: Time) -> Result<f64, DioError> {
+    if (1.0..=65535.0).contains(&length.get::<microsecond>()) {
+        Ok(length.get::<second>())
+    } else {
+        Err(DioError::ParameterOutOfRange)
+    }
+}
+
+fn is_pulsing(handle: wpihal_sys::HAL_DigitalHandle) -> Result<bool, DioError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let pulsing = unsafe { wpihal_sys::HAL_IsPulsing(handle, std::ptr::from_mut(&mut status)) };
+    DioError::from_status(status)?;
+    Ok(pulsing != 0)
+}
+
+/// Waits until the FPGA reports that none of the given pins are pulsing.
+async fn wait_for_pulse_end(
+    handles: impl Iterator<Item = wpihal_sys::HAL_DigitalHandle>,
+    seconds: f64,
+) -> Result<(), DioError> {
+    tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
+    // HAL_IsAnyPulsing only returns true if a pin on every port (headers, MXP and SPI)
+    // is pulsing, so each pin is checked individually instead
+    for handle in handles {
+        while is_pulsing(handle)? {
+            tokio::task::yield_now().await;
+        }
+    }
+    Ok(())
+}
+
+async fn pulse(handle: wpihal_sys::HAL_DigitalHandle, length: Time) -> Result<(), DioError> {
+    let seconds = pulse_length_seconds(length)?;
+    let _guard = PULSE_LOCK.lock().await;
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe { wpihal_sys::HAL_Pulse(handle, seconds, std::ptr::from_mut(&mut status)) };
+    DioError::from_status(status)?;
+    wait_for_pulse_end(std::iter::once(handle), seconds).await
+}
+
+impl<const N: u8> Dio<N, Output> {
+    /// Outputs a single pulse of the given length on this pin, resolving once the
+    /// FPGA reports the pulse has finished.
+    /// # Errors
+    /// Returns [`DioError::ParameterOutOfRange`] if `length` is not between
+    /// 1 and 65535 microseconds.
+    pub async fn pulse(&mut self, length: Time) -> Result<(), DioError> {
+        pulse(self.handle, length).await
+    }
+}
+
+impl AnyOutput {
+    /// Outputs a single pulse of the given length on this pin, resolving once the
+    /// FPGA reports the pulse has finished.
+    /// # Errors
+    /// Returns [`DioError::ParameterOutOfRange`] if `length` is not between
+    /// 1 and 65535 microseconds.
+    pub async fn pulse(&mut self, length: Time) -> Result<(), DioError> {
+        pulse(self.handle, length).await
+    }
+}
+
+/// Outputs a single pulse of the given length on several pins at once, resolving
+/// once the FPGA reports the pulse has finished on all of them.
+///
+/// wpihal only passes channels 0..=7, 9 and 10..=15 through to the FPGA when
+/// pulsing several pins, so other channels are rejected.
+/// # Errors
+/// Returns [`DioError::ParameterOutOfRange`] if `length` is not between
+/// 1 and 65535 microseconds, or [`DioError::OutOfRange`] if a pin's channel
+/// can't be pulsed alongside others.
+pub async fn pulse_multiple<'a>(
+    pins: impl IntoIterator<Item = &'a mut AnyOutput>,
+    length: Time,
+) -> Result<(), DioError> {
+    let seconds = pulse_length_seconds(length)?;
+    let pins: Vec<&AnyOutput> = pins.into_iter().map(|pin| &*pin).collect();
+    let mut mask = 0u32;
+    for pin in &pins {
+        if !matches!(pin.channel, 0..=7 | 9..=15) {
+            return Err(DioError::OutOfRange);
+        }
+        mask |= 1 << pin.channel;
+    }
+    let _guard = PULSE_LOCK.lock().await;
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe { wpihal_sys::HAL_PulseMultiple(mask, seconds, std::ptr::from_mut(&mut status)) };
+    DioError::from_status(status)?;
+    wait_for_pulse_end(pins.iter().map(|pin| pin.handle), seconds).await
+}
@@ -0,0 +1,56 @@
+//! Pins on the onboard SPI port.
+//!
+//! Chip selects 1..=3, MOSI and MISO of the onboard SPI port can be used as DIO pins
+//! instead. Like the MXP pins, they are handed out uninitialized, and consumed by
+//! [`frc::spi`](crate::spi) when used for SPI. Chip select 0 and the clock have no DIO channel.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{Dio, Uninitialized};
+
+macro_rules! spi_pin {
+    ($name:ident, $num:expr) => {
+        pub type $name = Dio<$num>;
+
+        impl crate::Sealed for Dio<$num, Uninitialized> {}
+    };
+}
+
+spi_pin!(Dio26, 26);
+spi_pin!(Dio27, 27);
+spi_pin!(Dio28, 28);
+spi_pin!(Dio29, 29);
+spi_pin!(Dio30, 30);
+
+/// The DIO channels of the onboard SPI port.
+pub struct SpiDioPort {
+    /// SPI CS1
+    pub dio26: Dio26,
+    /// SPI CS2
+    pub dio27: Dio27,
+    /// SPI CS3
+    pub dio28: Dio28,
+    /// SPI MOSI
+    pub dio29: Dio29,
+    /// SPI MISO
+    pub dio30: Dio30,
+}
+
+static SPI_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl SpiDioPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = SPI_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                dio26: Dio26::new_uninit(),
+                dio27: Dio27::new_uninit(),
+                dio28: Dio28::new_uninit(),
+                dio29: Dio29::new_uninit(),
+                dio30: Dio30::new_uninit(),
+            })
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Duty cycle inputs.
+//!
+//! The FPGA has 8 duty cycle decoders, which measure the frequency and duty cycle of a PWM
+//! signal on a digital source. This is how absolute encoders such as the REV Through Bore
+//! and CTRE Mag encoders report their position.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::mem::ManuallyDrop;
+
+use thiserror::Error;
+use uom::si::{
+    angle::revolution,
+    f64::{Angle, Frequency, Time},
+    frequency::hertz,
+    time::nanosecond,
+};
+
+use crate::dio::DigitalSource;
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum DutyCycleError {
+    #[error("all duty cycle inputs are in use")]
+    NoAvailableDutyCycles,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl DutyCycleError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(DutyCycleError::NoAvailableDutyCycles),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(DutyCycleError::ParameterOutOfRange),
+            a => Err(DutyCycleError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+/// A duty cycle decoder on a digital source, which can also be read as an absolute angle.
+///
+/// Absolute encoders don't use the full range of duty cycles: e.g. the REV Through Bore
+/// encoder outputs 1/1025 to 1024/1025. This range can be set with
+/// [`DutyCycleInput::set_duty_cycle_range`] so that [`DutyCycleInput::angle`] covers a full turn.
+pub struct DutyCycleInput<S: DigitalSource> {
+    handle: wpihal_sys::HAL_DutyCycleHandle,
+    source: S,
+    min_duty_cycle: f64,
+    max_duty_cycle: f64,
+    zero_offset: Angle,
+}
+
+impl<S: DigitalSource> DutyCycleInput<S> {
+    pub(crate) fn handle(&self) -> wpihal_sys::HAL_DutyCycleHandle {
+        self.handle
+    }
+
+    /// Creates a duty cycle decoder on a digital source.
+    /// # Errors
+    /// Returns [`DutyCycleError::NoAvailableDutyCycles`] if all duty cycle decoders are in use.
+    /// The source is returned with the error.
+    pub fn new(source: S) -> Result<Self, (DutyCycleError, S)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeDutyCycle(
+                source.source_handle(),
+                source.trigger_type(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        if let Err(e) = DutyCycleError::from_status(status) {
+            return Err((e, source));
+        }
+        Ok(Self {
+            handle,
+            source,
+            min_duty_cycle: 0.0,
+            max_duty_cycle: 1.0,
+            zero_offset: Angle::new::<revolution>(0.0),
+        })
+    }
+
+    /// Gets the frequency of the signal.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the decoder.
+    pub fn frequency(&self) -> Result<Frequency, DutyCycleError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let frequency = unsafe {
+            wpihal_sys::HAL_GetDutyCycleFrequency(self.handle, std::ptr::from_mut(&mut status))
+        };
+        DutyCycleError::from_status(status)?;
+        Ok(Frequency::new::<hertz>(f64::from(frequency)))
+    }
+
+    /// Gets how long the signal is high in each period.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the decoder.
+    pub fn high_time(&self) -> Result<Time, DutyCycleError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let high_time = unsafe {
+            wpihal_sys::HAL_GetDutyCycleHighTime(self.handle, std::ptr::from_mut(&mut status))
+        };
+        DutyCycleError::from_status(status)?;
+        Ok(Time::new::<nanosecond>(f64::from(high_time)))
+    }
+
+    /// Gets the duty cycle of the signal as a fraction from 0 to 1.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the decoder.
+    pub fn output(&self) -> Result<f64, DutyCycleError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let output = unsafe {
+            wpihal_sys::HAL_GetDutyCycleOutput(self.handle, std::ptr::from_mut(&mut status))
+        };
+        DutyCycleError::from_status(status)?;
+        Ok(output)
+    }
+
+    /// Sets the duty cycles corresponding to the start and end of a full turn.
+    /// Duty cycles outside this range are clamped to it.
+    /// # Errors
+    /// Returns [`DutyCycleError::ParameterOutOfRange`] unless `0 <= min < max <= 1`.
+    pub fn set_duty_cycle_range(&mut self, min: f64, max: f64) -> Result<(), DutyCycleError> {
+        // written so that NaN is rejected too
+        if !(0.0 <= min && min < max && max <= 1.0) {
+            return Err(DutyCycleError::ParameterOutOfRange);
+        }
+        self.min_duty_cycle = min;
+        self.max_duty_cycle = max;
+        Ok(())
+    }
+
+    /// Gets the angle which [`DutyCycleInput::angle`] reports as zero.
+    #[must_use]
+    pub fn zero_offset(&self) -> Angle {
+        self.zero_offset
+    }
+
+    /// Sets the angle which [`DutyCycleInput::angle`] reports as zero, e.g. to account
+    /// for how an encoder was mounted on a swerve module.
+    pub fn set_zero_offset(&mut self, zero_offset: Angle) {
+        self.zero_offset = zero_offset;
+    }
+
+    /// Gets the absolute angle of the encoder, from 0 to 1 revolution after the zero offset.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the decoder.
+    pub fn angle(&self) -> Result<Angle, DutyCycleError> {
+        let output = self
+            .output()?
+            .clamp(self.min_duty_cycle, self.max_duty_cycle);
+        let turns = (output - self.min_duty_cycle) / (self.max_duty_cycle - self.min_duty_cycle);
+        let turns = (turns - self.zero_offset.get::<revolution>()).rem_euclid(1.0);
+        Ok(Angle::new::<revolution>(turns))
+    }
+
+    /// Frees the duty cycle decoder, returning its source.
+    #[must_use]
+    pub fn into_source(self) -> S {
+        let this = ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_FreeDutyCycle(this.handle) };
+        // SAFETY: `this` is never dropped, so the source is moved out exactly once.
+        unsafe { std::ptr::read(&this.source) }
+    }
+}
+
+impl<S: DigitalSource> Drop for DutyCycleInput<S> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeDutyCycle(self.handle) };
+    }
+}
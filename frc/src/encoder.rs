@@ -0,0 +1,412 @@
+//! Quadrature encoders.
+//!
+//! An [`Encoder`] decodes the A and B channels of a quadrature encoder in the FPGA, and
+//! optionally resets its count on an index pulse. Distances are reported in the units of the
+//! configured distance per pulse: a [`Length`] for linear mechanisms (e.g. a drivetrain wheel),
+//! or an [`Angle`] for rotating ones (e.g. an arm).
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::mem::ManuallyDrop;
+
+use thiserror::Error;
+use uom::si::{
+    angle::radian,
+    angular_velocity::radian_per_second,
+    f64::{Angle, AngularVelocity, Length, Velocity},
+    length::meter,
+    velocity::meter_per_second,
+};
+
+use crate::counter::NoSource;
+use crate::dio::DigitalSource;
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum EncoderError {
+    #[error("all encoders are in use")]
+    NoAvailableEncoders,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl EncoderError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(EncoderError::NoAvailableEncoders),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(EncoderError::ParameterOutOfRange),
+            a => Err(EncoderError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+/// Which edges of the A and B channels are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodingType {
+    /// Count rising edges of A. 1x and 2x encoders use one of the FPGA's counters.
+    X1,
+    /// Count both edges of A.
+    X2,
+    /// Count both edges of A and B, for the highest resolution.
+    X4,
+}
+
+impl From<EncodingType> for wpihal_sys::HAL_EncoderEncodingType {
+    fn from(value: EncodingType) -> Self {
+        match value {
+            EncodingType::X1 => wpihal_sys::HAL_EncoderEncodingType_HAL_Encoder_k1X,
+            EncodingType::X2 => wpihal_sys::HAL_EncoderEncodingType_HAL_Encoder_k2X,
+            EncodingType::X4 => wpihal_sys::HAL_EncoderEncodingType_HAL_Encoder_k4X,
+        }
+    }
+}
+
+/// When the index pulse resets the encoder count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexingType {
+    ResetWhileHigh,
+    ResetWhileLow,
+    ResetOnFallingEdge,
+    ResetOnRisingEdge,
+}
+
+impl From<IndexingType> for wpihal_sys::HAL_EncoderIndexingType {
+    fn from(value: IndexingType) -> Self {
+        match value {
+            IndexingType::ResetWhileHigh => wpihal_sys::HAL_EncoderIndexingType_HAL_kResetWhileHigh,
+            IndexingType::ResetWhileLow => wpihal_sys::HAL_EncoderIndexingType_HAL_kResetWhileLow,
+            IndexingType::ResetOnFallingEdge => {
+                wpihal_sys::HAL_EncoderIndexingType_HAL_kResetOnFallingEdge
+            }
+            IndexingType::ResetOnRisingEdge => {
+                wpihal_sys::HAL_EncoderIndexingType_HAL_kResetOnRisingEdge
+            }
+        }
+    }
+}
+
+/// A quantity which an encoder can measure distance in: [`Length`] or [`Angle`].
+/// wpihal works in the SI base unit of the quantity (meters or radians).
+pub trait EncoderDistance: crate::Sealed + Copy {
+    /// The rate of change of the distance: [`Velocity`] or [`AngularVelocity`].
+    type Rate;
+
+    fn to_base(self) -> f64;
+    fn from_base(value: f64) -> Self;
+    fn rate_to_base(rate: Self::Rate) -> f64;
+    fn rate_from_base(value: f64) -> Self::Rate;
+}
+
+impl crate::Sealed for Length {}
+
+impl EncoderDistance for Length {
+    type Rate = Velocity;
+
+    fn to_base(self) -> f64 {
+        self.get::<meter>()
+    }
+
+    fn from_base(value: f64) -> Self {
+        Length::new::<meter>(value)
+    }
+
+    fn rate_to_base(rate: Self::Rate) -> f64 {
+        rate.get::<meter_per_second>()
+    }
+
+    fn rate_from_base(value: f64) -> Self::Rate {
+        Velocity::new::<meter_per_second>(value)
+    }
+}
+
+impl crate::Sealed for Angle {}
+
+impl EncoderDistance for Angle {
+    type Rate = AngularVelocity;
+
+    fn to_base(self) -> f64 {
+        self.get::<radian>()
+    }
+
+    fn from_base(value: f64) -> Self {
+        Angle::new::<radian>(value)
+    }
+
+    fn rate_to_base(rate: Self::Rate) -> f64 {
+        rate.get::<radian_per_second>()
+    }
+
+    fn rate_from_base(value: f64) -> Self::Rate {
+        AngularVelocity::new::<radian_per_second>(value)
+    }
+}
+
+/// A quadrature encoder on the sources `A` and `B`, with an optional `Index` source,
+/// measuring distance as `D`.
+pub struct Encoder<A: DigitalSource, B: DigitalSource, D: EncoderDistance, Index = NoSource> {
+    handle: wpihal_sys::HAL_EncoderHandle,
+    a: A,
+    b: B,
+    index: Index,
+    distance_per_pulse: D,
+}
+
+impl<A: DigitalSource, B: DigitalSource, D: EncoderDistance> Encoder<A, B, D> {
+    /// Creates an encoder on the A and B channels, which moves `distance_per_pulse`
+    /// for each pulse of the encoder (not each decoded edge).
+    /// # Errors
+    /// Returns [`EncoderError::NoAvailableEncoders`] if all encoders (or counters, for
+    /// 1x and 2x decoding) are in use. The sources are returned with the error.
+    pub fn new(
+        a: A,
+        b: B,
+        encoding: EncodingType,
+        distance_per_pulse: D,
+    ) -> Result<Self, (EncoderError, (A, B))> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeEncoder(
+                a.source_handle(),
+                a.trigger_type(),
+                b.source_handle(),
+                b.trigger_type(),
+                i32::from(false),
+                encoding.into(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        if let Err(e) = EncoderError::from_status(status) {
+            return Err((e, (a, b)));
+        }
+        let mut encoder = Self {
+            handle,
+            a,
+            b,
+            index: NoSource,
+            distance_per_pulse,
+        };
+        match encoder.set_distance_per_pulse(distance_per_pulse) {
+            Ok(()) => Ok(encoder),
+            Err(e) => {
+                let (a, b, NoSource) = encoder.into_sources();
+                Err((e, (a, b)))
+            }
+        }
+    }
+
+    /// Resets the count whenever the index source matches `indexing`.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the encoder. The encoder is unchanged,
+    /// and is returned with the index source and the error.
+    #[allow(clippy::type_complexity)]
+    pub fn with_index<Index: DigitalSource>(
+        self,
+        index: Index,
+        indexing: IndexingType,
+    ) -> Result<Encoder<A, B, D, Index>, (EncoderError, Self, Index)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetEncoderIndexSource(
+                self.handle,
+                index.source_handle(),
+                index.trigger_type(),
+                indexing.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        if let Err(e) = EncoderError::from_status(status) {
+            return Err((e, self, index));
+        }
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so its fields are moved out exactly once.
+        unsafe {
+            Ok(Encoder {
+                handle: this.handle,
+                a: std::ptr::read(&this.a),
+                b: std::ptr::read(&this.b),
+                index,
+                distance_per_pulse: this.distance_per_pulse,
+            })
+        }
+    }
+}
+
+impl<A: DigitalSource, B: DigitalSource, D: EncoderDistance, Index> Encoder<A, B, D, Index> {
+    /// Gets the current count, scaled by the decoding type so that it counts encoder pulses.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn count(&self) -> Result<i32, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let count =
+            unsafe { wpihal_sys::HAL_GetEncoder(self.handle, std::ptr::from_mut(&mut status)) };
+        EncoderError::from_status(status)?;
+        Ok(count)
+    }
+
+    /// Gets the raw count of decoded edges, e.g. 4 per pulse with 4x decoding.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn raw_count(&self) -> Result<i32, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let count =
+            unsafe { wpihal_sys::HAL_GetEncoderRaw(self.handle, std::ptr::from_mut(&mut status)) };
+        EncoderError::from_status(status)?;
+        Ok(count)
+    }
+
+    /// Resets the count, and therefore the distance, to zero.
+    /// # Errors
+    /// Returns an error if wpihal fails to reset the encoder.
+    pub fn reset(&mut self) -> Result<(), EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_ResetEncoder(self.handle, std::ptr::from_mut(&mut status)) };
+        EncoderError::from_status(status)
+    }
+
+    /// Gets the distance travelled since the last reset.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn distance(&self) -> Result<D, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let distance = unsafe {
+            wpihal_sys::HAL_GetEncoderDistance(self.handle, std::ptr::from_mut(&mut status))
+        };
+        EncoderError::from_status(status)?;
+        Ok(D::from_base(distance))
+    }
+
+    /// Gets the current rate, based on the period between the most recent pulses.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn rate(&self) -> Result<D::Rate, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let rate =
+            unsafe { wpihal_sys::HAL_GetEncoderRate(self.handle, std::ptr::from_mut(&mut status)) };
+        EncoderError::from_status(status)?;
+        Ok(D::rate_from_base(rate))
+    }
+
+    /// Gets the configured distance per encoder pulse.
+    #[must_use]
+    pub fn distance_per_pulse(&self) -> D {
+        self.distance_per_pulse
+    }
+
+    /// Sets the distance moved for each encoder pulse.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the encoder.
+    pub fn set_distance_per_pulse(&mut self, distance_per_pulse: D) -> Result<(), EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetEncoderDistancePerPulse(
+                self.handle,
+                distance_per_pulse.to_base(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        EncoderError::from_status(status)?;
+        self.distance_per_pulse = distance_per_pulse;
+        Ok(())
+    }
+
+    /// Sets the rate below which the encoder is considered stopped. See [`Encoder::stopped`].
+    /// # Errors
+    /// Returns [`EncoderError::ParameterOutOfRange`] if `min_rate` is zero, or if the
+    /// distance per pulse hasn't been set.
+    pub fn set_min_rate(&mut self, min_rate: D::Rate) -> Result<(), EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetEncoderMinRate(
+                self.handle,
+                D::rate_to_base(min_rate),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        EncoderError::from_status(status)
+    }
+
+    /// Checks whether the encoder is moving slower than the minimum rate.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn stopped(&self) -> Result<bool, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let stopped = unsafe {
+            wpihal_sys::HAL_GetEncoderStopped(self.handle, std::ptr::from_mut(&mut status))
+        };
+        EncoderError::from_status(status)?;
+        Ok(stopped != 0)
+    }
+
+    /// Gets the direction of the most recent movement: true for forwards, false for backwards.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the encoder.
+    pub fn direction(&self) -> Result<bool, EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let direction = unsafe {
+            wpihal_sys::HAL_GetEncoderDirection(self.handle, std::ptr::from_mut(&mut status))
+        };
+        EncoderError::from_status(status)?;
+        Ok(direction != 0)
+    }
+
+    /// Inverts the direction of the encoder.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the encoder.
+    pub fn set_reverse_direction(&mut self, reverse: bool) -> Result<(), EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetEncoderReverseDirection(
+                self.handle,
+                i32::from(reverse),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        EncoderError::from_status(status)
+    }
+
+    /// Sets the number of samples (1 to 127) that the rate is averaged over.
+    /// # Errors
+    /// Returns [`EncoderError::ParameterOutOfRange`] if `samples` is out of range.
+    pub fn set_samples_to_average(&mut self, samples: u8) -> Result<(), EncoderError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetEncoderSamplesToAverage(
+                self.handle,
+                samples.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        EncoderError::from_status(status)
+    }
+
+    /// Frees the encoder, returning its A, B and index sources.
+    #[must_use]
+    pub fn into_sources(self) -> (A, B, Index) {
+        let this = ManuallyDrop::new(self);
+        this.free();
+        // SAFETY: `this` is never dropped, so the sources are moved out exactly once.
+        unsafe {
+            (
+                std::ptr::read(&this.a),
+                std::ptr::read(&this.b),
+                std::ptr::read(&this.index),
+            )
+        }
+    }
+
+    fn free(&self) {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_FreeEncoder(self.handle, std::ptr::from_mut(&mut status)) };
+    }
+}
+
+impl<A: DigitalSource, B: DigitalSource, D: EncoderDistance, Index> Drop
+    for Encoder<A, B, D, Index>
+{
+    fn drop(&mut self) {
+        self.free();
+    }
+}
@@ -30,3 +30,17 @@ impl Display for HalError {
 }
 
 impl Error for HalError {}
+
+/// Resolves a status of `HAL_USE_LAST_ERROR` into the underlying error code.
+/// wpihal reports some errors (e.g. allocation failures) by storing the real status
+/// in a thread-local "last error", so this must be called on the thread that made
+/// the failing call. Other statuses are returned unchanged.
+pub(crate) fn resolve_status(status: i32) -> i32 {
+    if status == wpihal_sys::HAL_USE_LAST_ERROR {
+        let mut status = status;
+        unsafe { wpihal_sys::HAL_GetLastError(std::ptr::from_mut(&mut status)) };
+        status
+    } else {
+        status
+    }
+}
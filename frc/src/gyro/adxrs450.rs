@@ -0,0 +1,582 @@
+//! The Analog Devices ADXRS450 SPI gyro, such as in the FRC gyro board for the onboard SPI port.
+//!
+//! The ADXRS450 is sampled at 2 kHz by auto-SPI, and the samples are integrated on a
+//! background thread to measure its angle. Its zero-rate output varies between gyros, so it
+//! must be calibrated with [`Adxrs450::calibrate`] while the robot is disabled and still.
+//!
+//! The command protocol is exposed through [`read_register`] and [`decode_rate`], which work
+//! with any [`SpiDevice`].
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    convert::Infallible,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use embedded_hal::spi::{SpiDevice, MODE_0};
+use thiserror::Error;
+use uom::si::{
+    angle::degree,
+    angular_velocity::degree_per_second,
+    f64::{Angle, AngularVelocity, Frequency, Time},
+    frequency::megahertz,
+    time::second,
+};
+
+use super::Gyro;
+use crate::reactor::driver_station::{DriverStation, RobotMode};
+use crate::spi::{AutoSpi, AutoSpiFrame, ChipSelectPolarity, Spi, SpiError, SpiPins};
+
+/// The sensitivity of the ADXRS450
+pub const DEGREES_PER_SECOND_PER_LSB: f64 = 0.0125;
+
+/// How long the gyro is sampled for during calibration, in seconds
+pub const CALIBRATION_TIME: f64 = 5.0;
+
+/// How long between samples, in seconds
+const SAMPLE_PERIOD: f64 = 0.0005;
+
+/// How long to let the samples settle before calibrating, in seconds
+const SETTLE_TIME: f64 = 0.1;
+
+/// How often calibration checks whether the robot has been enabled, in seconds
+const ENABLED_CHECK_PERIOD: f64 = 0.02;
+
+/// How many frames the FPGA buffers before dropping them
+const BUFFERED_FRAMES: usize = 2048;
+
+/// Commands and responses are 32 bits, sent most significant byte first.
+const FRAME_LEN: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum Adxrs450Error<E: Debug = SpiError> {
+    #[error("no ADXRS450 found; read part ID {0:#06x}")]
+    NotFound(u16),
+    #[error("ADXRS450 sent an invalid response")]
+    InvalidResponse,
+    #[error("robot was enabled during calibration")]
+    RobotEnabled,
+    #[error("SPI transfer failed: {0:?}")]
+    Spi(E),
+}
+
+/// The ADXRS450's readable registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    Rate,
+    Temperature,
+    Fault,
+    PartId,
+    SerialNumberHigh,
+    SerialNumberLow,
+}
+
+impl Register {
+    fn address(self) -> u32 {
+        match self {
+            Register::Rate => 0x00,
+            Register::Temperature => 0x02,
+            Register::Fault => 0x0A,
+            Register::PartId => 0x0C,
+            Register::SerialNumberHigh => 0x0E,
+            Register::SerialNumberLow => 0x10,
+        }
+    }
+}
+
+/// Sets the parity bit of a command, so that it has an odd number of ones.
+const fn with_parity(command: u32) -> u32 {
+    if command.count_ones() & 1 == 0 {
+        command | 1
+    } else {
+        command
+    }
+}
+
+/// Requests the current rate, which is returned in the response to the next command.
+pub const SENSOR_DATA_COMMAND: u32 = with_parity(0x2000_0000);
+
+/// Reads a 16-bit register. The ADXRS450 responds to each command during the next
+/// transfer, so this takes two transfers.
+/// # Errors
+/// Returns [`Adxrs450Error::InvalidResponse`] if the gyro responds with an error or bad parity.
+pub fn read_register<D: SpiDevice>(
+    spi: &mut D,
+    register: Register,
+) -> Result<u16, Adxrs450Error<D::Error>> {
+    let command = with_parity(0x8000_0000 | register.address() << 17);
+    spi.write(&command.to_be_bytes())
+        .map_err(Adxrs450Error::Spi)?;
+    let mut response = [0; FRAME_LEN];
+    spi.read(&mut response).map_err(Adxrs450Error::Spi)?;
+    let response = u32::from_be_bytes(response);
+    // read responses start with 0b010, and have odd parity
+    if response >> 29 != 0b010 || response.count_ones() & 1 == 0 {
+        return Err(Adxrs450Error::InvalidResponse);
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    Ok((response >> 5) as u16)
+}
+
+/// Decodes the response to [`SENSOR_DATA_COMMAND`] into a rate in units of
+/// [`DEGREES_PER_SECOND_PER_LSB`]. Returns `None` if the response isn't valid sensor data,
+/// or reports a fault.
+#[must_use]
+pub fn decode_rate(response: u32) -> Option<i16> {
+    // status bits 27:26 are 0b01 for valid data, and bits 3:1 are fault flags
+    let valid = response & 0x0C00_000E == 0x0400_0000 && response.count_ones() & 1 == 1;
+    #[allow(clippy::cast_possible_truncation)]
+    valid.then_some((response >> 10) as u16 as i16)
+}
+
+/// Samples integrated so far during calibration
+#[derive(Default)]
+struct CalibrationSums {
+    /// LSB seconds
+    integral: f64,
+    /// seconds
+    duration: f64,
+}
+
+/// The state updated by the sampling thread. Rates are in LSB, and angles in LSB seconds.
+#[derive(Default)]
+struct Integrator {
+    zero_rate: f64,
+    rate: f64,
+    angle: f64,
+    last_timestamp: Option<f64>,
+    calibration: Option<CalibrationSums>,
+    /// Samples which couldn't be read, or which the gyro reported as invalid
+    bad_samples: u64,
+}
+
+impl Integrator {
+    fn update(&mut self, frame: &AutoSpiFrame) {
+        let raw = <[u8; FRAME_LEN]>::try_from(frame.data.as_slice())
+            .ok()
+            .and_then(|response| decode_rate(u32::from_be_bytes(response)));
+        let Some(raw) = raw else {
+            self.bad_samples += 1;
+            return;
+        };
+        let raw = f64::from(raw);
+        let now = frame.timestamp.get::<second>();
+        if let Some(last) = self.last_timestamp.replace(now) {
+            let dt = now - last;
+            self.angle += (raw - self.zero_rate) * dt;
+            if let Some(sums) = &mut self.calibration {
+                sums.integral += raw * dt;
+                sums.duration += dt;
+            }
+        }
+        self.rate = raw - self.zero_rate;
+    }
+
+    /// Sets the zero-rate output to the mean rate sampled during calibration, if any
+    /// samples were taken, and resets the angle.
+    fn finish_calibration(&mut self) {
+        if let Some(sums) = self.calibration.take() {
+            if sums.duration > 0.0 {
+                self.zero_rate = sums.integral / sums.duration;
+            }
+        }
+        self.angle = 0.0;
+    }
+}
+
+/// Discards an unfinished calibration when dropped, keeping the previous zero-rate output.
+struct CalibrationGuard<'a>(&'a Mutex<Integrator>);
+
+impl Drop for CalibrationGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.lock() {
+            state.calibration = None;
+        }
+    }
+}
+
+/// Configures an SPI port for the gyro, and checks that it's an ADXRS450.
+fn identify<P: SpiPins>(spi: &mut Spi<P>) -> Result<(), Adxrs450Error> {
+    spi.set_speed(Frequency::new::<megahertz>(3.0));
+    spi.set_mode(MODE_0);
+    spi.set_chip_select_polarity(ChipSelectPolarity::ActiveLow)
+        .map_err(Adxrs450Error::Spi)?;
+    let part_id = read_register(spi, Register::PartId)?;
+    if part_id & 0xFF00 != 0x5200 {
+        return Err(Adxrs450Error::NotFound(part_id));
+    }
+    Ok(())
+}
+
+/// An ADXRS450 gyro on an SPI port, which is sampled by auto-SPI. As only one port can use
+/// auto-SPI, only one can exist at a time.
+pub struct Adxrs450<P: SpiPins + Send + 'static> {
+    state: Arc<Mutex<Integrator>>,
+    stop: Arc<AtomicBool>,
+    sampler: Option<JoinHandle<Spi<P>>>,
+}
+
+impl<P: SpiPins + Send + 'static> Adxrs450<P> {
+    /// Configures the SPI port for the gyro and starts sampling it. The gyro is uncalibrated;
+    /// call [`Adxrs450::calibrate`] while the robot is still, or restore a previous calibration
+    /// with [`Adxrs450::set_zero_rate`].
+    /// # Errors
+    /// Returns [`Adxrs450Error::NotFound`] if there's no ADXRS450 on the port, or
+    /// [`Adxrs450Error::Spi`] if the port can't be configured, e.g. because auto-SPI is already
+    /// in use. The port is returned with the error.
+    /// # Panics
+    /// Panics if the sampling thread panics while starting.
+    pub fn new(mut spi: Spi<P>) -> Result<Self, (Adxrs450Error, Spi<P>)> {
+        if let Err(e) = identify(&mut spi) {
+            return Err((e, spi));
+        }
+        let mut auto = AutoSpi::new(spi, (FRAME_LEN + 1) * BUFFERED_FRAMES)
+            .map_err(|(e, spi)| (Adxrs450Error::Spi(e), spi))?;
+        if let Err(e) = auto.set_transmit_data(&SENSOR_DATA_COMMAND.to_be_bytes(), 0) {
+            return Err((Adxrs450Error::Spi(e), auto.into_spi()));
+        }
+
+        let state = Arc::new(Mutex::new(Integrator::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (started_tx, started_rx) = std::sync::mpsc::sync_channel(1);
+        let sampler = std::thread::spawn({
+            let state = Arc::clone(&state);
+            let stop = Arc::clone(&stop);
+            move || {
+                let mut auto = auto;
+                match auto.start_rate(Time::new::<second>(SAMPLE_PERIOD)) {
+                    Ok(frames) => {
+                        let _ = started_tx.send(Ok(()));
+                        // check for a stop on every item, as a failing read still yields one
+                        for frame in futures::executor::block_on_stream(frames) {
+                            if stop.load(Ordering::Relaxed) {
+                                break;
+                            }
+                            let mut state = state.lock().unwrap();
+                            match frame {
+                                Ok(frame) => state.update(&frame),
+                                Err(_) => state.bad_samples += 1,
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = started_tx.send(Err(e));
+                    }
+                }
+                auto.into_spi()
+            }
+        });
+        let gyro = Self {
+            state,
+            stop,
+            sampler: Some(sampler),
+        };
+        match started_rx.recv() {
+            Ok(Ok(())) => Ok(gyro),
+            Ok(Err(e)) => Err((Adxrs450Error::Spi(e), gyro.into_spi())),
+            Err(_) => panic!("ADXRS450 sampling thread panicked"),
+        }
+    }
+
+    /// Measures the zero-rate output of the gyro, which takes 5 seconds, then resets the
+    /// angle to zero. The robot must be disabled and still during calibration. Sampling
+    /// continues while calibrating, so this doesn't block the executor.
+    /// # Errors
+    /// Returns [`Adxrs450Error::RobotEnabled`] if the robot is enabled before calibration
+    /// finishes, in which case the previous calibration is kept.
+    pub async fn calibrate(&mut self) -> Result<(), Adxrs450Error> {
+        tokio::time::sleep(Duration::from_secs_f64(SETTLE_TIME)).await;
+        self.state.lock().unwrap().calibration = Some(CalibrationSums::default());
+        // stops calibrating if the robot is enabled or this future is dropped
+        let _guard = CalibrationGuard(&self.state);
+        let deadline = Instant::now() + Duration::from_secs_f64(CALIBRATION_TIME);
+        while Instant::now() < deadline {
+            if DriverStation::get_robot_mode() != RobotMode::Disabled {
+                return Err(Adxrs450Error::RobotEnabled);
+            }
+            tokio::time::sleep(Duration::from_secs_f64(ENABLED_CHECK_PERIOD)).await;
+        }
+        self.state.lock().unwrap().finish_calibration();
+        Ok(())
+    }
+
+    /// Gets the zero-rate output of the gyro, which can be saved and restored with
+    /// [`Adxrs450::set_zero_rate`] to skip calibration.
+    #[must_use]
+    pub fn zero_rate(&self) -> AngularVelocity {
+        let zero_rate = self.state.lock().unwrap().zero_rate;
+        AngularVelocity::new::<degree_per_second>(zero_rate * DEGREES_PER_SECOND_PER_LSB)
+    }
+
+    /// Restores a zero-rate output from [`Adxrs450::zero_rate`], and resets the angle to zero.
+    pub fn set_zero_rate(&mut self, zero_rate: AngularVelocity) {
+        let mut state = self.state.lock().unwrap();
+        state.zero_rate = zero_rate.get::<degree_per_second>() / DEGREES_PER_SECOND_PER_LSB;
+        state.angle = 0.0;
+    }
+
+    /// Gets the angle turned since the gyro was last reset. Positive angles are clockwise,
+    /// and the angle is continuous rather than wrapping at 360 degrees.
+    #[must_use]
+    pub fn angle(&self) -> Angle {
+        let angle = self.state.lock().unwrap().angle;
+        Angle::new::<degree>(angle * DEGREES_PER_SECOND_PER_LSB)
+    }
+
+    /// Gets the most recently sampled rate of rotation. Positive rates are clockwise.
+    #[must_use]
+    pub fn rate(&self) -> AngularVelocity {
+        let rate = self.state.lock().unwrap().rate;
+        AngularVelocity::new::<degree_per_second>(rate * DEGREES_PER_SECOND_PER_LSB)
+    }
+
+    /// Gets the number of samples which couldn't be read from auto-SPI, or which the gyro
+    /// reported as invalid. These samples are skipped.
+    #[must_use]
+    pub fn bad_samples(&self) -> u64 {
+        self.state.lock().unwrap().bad_samples
+    }
+
+    /// Resets the angle of the gyro to zero.
+    pub fn reset(&mut self) {
+        self.state.lock().unwrap().angle = 0.0;
+    }
+
+    /// Stops sampling the gyro, returning its SPI port.
+    /// # Panics
+    /// Panics if the sampling thread panicked.
+    #[must_use]
+    pub fn into_spi(mut self) -> Spi<P> {
+        self.stop_sampling()
+            .expect("ADXRS450 sampling thread panicked")
+    }
+
+    fn stop_sampling(&mut self) -> Option<Spi<P>> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.sampler.take()?.join().ok()
+    }
+}
+
+impl<P: SpiPins + Send + 'static> Gyro for Adxrs450<P> {
+    type Error = Infallible;
+
+    fn angle(&mut self) -> Result<Angle, Infallible> {
+        Ok(Adxrs450::angle(self))
+    }
+
+    fn rate(&mut self) -> Result<AngularVelocity, Infallible> {
+        Ok(Adxrs450::rate(self))
+    }
+
+    fn reset(&mut self) -> Result<(), Infallible> {
+        Adxrs450::reset(self);
+        Ok(())
+    }
+}
+
+impl<P: SpiPins + Send + 'static> Drop for Adxrs450<P> {
+    fn drop(&mut self) {
+        let _ = self.stop_sampling();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use embedded_hal::spi::{ErrorType, Operation};
+    use uom::si::time::microsecond;
+
+    use super::*;
+
+    /// Records the bytes written, and reads back queued bytes.
+    #[derive(Default)]
+    struct MockSpi {
+        written: Vec<u8>,
+        responses: VecDeque<u8>,
+    }
+
+    impl MockSpi {
+        fn responding(response: u32) -> Self {
+            Self {
+                written: Vec::new(),
+                responses: response.to_be_bytes().into(),
+            }
+        }
+
+        fn respond(&mut self, bytes: &mut [u8]) {
+            for byte in bytes {
+                *byte = self.responses.pop_front().expect("no response queued");
+            }
+        }
+    }
+
+    impl ErrorType for MockSpi {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for MockSpi {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.written.extend_from_slice(bytes),
+                    Operation::Read(bytes) => self.respond(bytes),
+                    Operation::Transfer(read, write) => {
+                        self.written.extend_from_slice(write);
+                        self.respond(read);
+                    }
+                    Operation::TransferInPlace(bytes) => {
+                        self.written.extend_from_slice(bytes);
+                        self.respond(bytes);
+                    }
+                    Operation::DelayNs(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A response to a register read
+    fn register_response(value: u16) -> u32 {
+        with_parity(0b010 << 29 | u32::from(value) << 5)
+    }
+
+    /// A response to the sensor data command
+    fn sensor_response(status: u32, rate: i16, faults: u32) -> u32 {
+        #[allow(clippy::cast_sign_loss)]
+        with_parity(status << 26 | u32::from(rate as u16) << 10 | faults << 1)
+    }
+
+    fn frame(microseconds: f64, rate: i16) -> AutoSpiFrame {
+        AutoSpiFrame {
+            timestamp: Time::new::<microsecond>(microseconds),
+            data: sensor_response(0b01, rate, 0).to_be_bytes().to_vec(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn commands_have_odd_parity() {
+        assert_eq!(SENSOR_DATA_COMMAND.count_ones() & 1, 1);
+        assert_eq!(with_parity(0x2000_0000), 0x2000_0000);
+        assert_eq!(with_parity(0x8000_0000 | 0x0C << 17), 0x8018_0000);
+    }
+
+    #[test]
+    fn read_register_sends_command_and_decodes_response() {
+        let mut spi = MockSpi::responding(register_response(0x5201));
+        assert_eq!(read_register(&mut spi, Register::PartId).unwrap(), 0x5201);
+        assert_eq!(spi.written, 0x8018_0000_u32.to_be_bytes());
+    }
+
+    #[test]
+    fn read_register_rejects_error_response() {
+        let response = with_parity(u32::from(0x5201_u16) << 5);
+        let mut spi = MockSpi::responding(response);
+        assert!(matches!(
+            read_register(&mut spi, Register::PartId),
+            Err(Adxrs450Error::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn read_register_rejects_bad_parity() {
+        let mut spi = MockSpi::responding(register_response(0x5201) ^ 1);
+        assert!(matches!(
+            read_register(&mut spi, Register::PartId),
+            Err(Adxrs450Error::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn decode_rate_accepts_valid_data() {
+        assert_eq!(decode_rate(sensor_response(0b01, 100, 0)), Some(100));
+        assert_eq!(decode_rate(sensor_response(0b01, -100, 0)), Some(-100));
+    }
+
+    #[test]
+    fn decode_rate_rejects_invalid_data() {
+        assert_eq!(decode_rate(sensor_response(0b01, 100, 0b010)), None);
+        assert_eq!(decode_rate(sensor_response(0b10, 100, 0)), None);
+        assert_eq!(decode_rate(sensor_response(0b01, 100, 0) ^ 1), None);
+    }
+
+    #[test]
+    fn integrator_integrates_centered_rate() {
+        let mut integrator = Integrator {
+            zero_rate: 10.0,
+            ..Integrator::default()
+        };
+        integrator.update(&frame(0.0, 10));
+        assert_close(integrator.angle, 0.0);
+        integrator.update(&frame(1_000_000.0, 90));
+        assert_close(integrator.angle, 80.0);
+        assert_close(integrator.rate, 80.0);
+        integrator.update(&frame(1_500_000.0, -30));
+        assert_close(integrator.angle, 60.0);
+        assert_close(integrator.rate, -40.0);
+        assert_eq!(integrator.bad_samples, 0);
+    }
+
+    #[test]
+    fn integrator_skips_invalid_samples() {
+        let mut integrator = Integrator::default();
+        integrator.update(&frame(0.0, 10));
+        integrator.update(&AutoSpiFrame {
+            timestamp: Time::new::<microsecond>(500.0),
+            data: sensor_response(0b01, 10, 0b001).to_be_bytes().to_vec(),
+        });
+        integrator.update(&AutoSpiFrame {
+            timestamp: Time::new::<microsecond>(1_000.0),
+            data: vec![0; 2],
+        });
+        assert_eq!(integrator.bad_samples, 2);
+        assert_close(integrator.angle, 0.0);
+    }
+
+    #[test]
+    fn calibration_measures_mean_rate() {
+        let mut integrator = Integrator {
+            calibration: Some(CalibrationSums::default()),
+            ..Integrator::default()
+        };
+        integrator.update(&frame(0.0, 20));
+        integrator.update(&frame(500_000.0, 20));
+        integrator.update(&frame(1_000_000.0, 40));
+        let sums = integrator.calibration.as_ref().unwrap();
+        assert_close(sums.integral, 30.0);
+        assert_close(sums.duration, 1.0);
+        integrator.finish_calibration();
+        assert_close(integrator.zero_rate, 30.0);
+        assert_close(integrator.angle, 0.0);
+        assert!(integrator.calibration.is_none());
+        integrator.update(&frame(1_500_000.0, 40));
+        assert_close(integrator.angle, 5.0);
+    }
+
+    #[test]
+    fn cancelled_calibration_keeps_zero_rate() {
+        let state = Mutex::new(Integrator {
+            zero_rate: 12.0,
+            calibration: Some(CalibrationSums::default()),
+            ..Integrator::default()
+        });
+        drop(CalibrationGuard(&state));
+        let integrator = state.lock().unwrap();
+        assert!(integrator.calibration.is_none());
+        assert_close(integrator.zero_rate, 12.0);
+    }
+}
@@ -0,0 +1,34 @@
+//! Gyros.
+//!
+//! The [`Gyro`] trait is implemented by every gyro in this crate, including the
+//! [`AnalogGyro`](crate::analog::AnalogGyro) and the SPI [`Adxrs450`], so code which reads
+//! heading can be written once for any of them.
+
+#![allow(clippy::module_name_repetitions)]
+
+pub mod adxrs450;
+
+pub use adxrs450::*;
+
+use uom::si::f64::{Angle, AngularVelocity};
+
+/// A single-axis gyro. Positive angles and rates are clockwise.
+pub trait Gyro {
+    type Error;
+
+    /// Gets the angle turned since the gyro was last reset. The angle is continuous
+    /// rather than wrapping at 360 degrees.
+    /// # Errors
+    /// Returns an error if the gyro can't be read.
+    fn angle(&mut self) -> Result<Angle, Self::Error>;
+
+    /// Gets the rate of rotation.
+    /// # Errors
+    /// Returns an error if the gyro can't be read.
+    fn rate(&mut self) -> Result<AngularVelocity, Self::Error>;
+
+    /// Resets the angle to zero.
+    /// # Errors
+    /// Returns an error if the gyro can't be reset.
+    fn reset(&mut self) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,322 @@
+//! I2C buses, implementing the [`embedded_hal`] and [`embedded_hal_async`] I2C traits.
+//!
+//! The roboRIO has 2 I2C ports: one onboard, and one on the MXP. The onboard port is taken
+//! with [`OnboardI2cPins::take`], and the MXP port consumes MXP pins 24 and 25 so they can't
+//! also be used as DIO. Either is turned into an [`I2c`] bus, so any embedded-hal I2C
+//! driver can be used on the roboRIO.
+//!
+//! The roboRIO's I2C controller only supports writes, reads, and a write followed by a read
+//! with a repeated start. Other sequences of operations are split into several transfers.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use embedded_hal::i2c::{ErrorKind, ErrorType, NoAcknowledgeSource, Operation, SevenBitAddress};
+use thiserror::Error;
+
+use crate::dio::{Dio24, Dio25};
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum I2cError {
+    #[error("port is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("transfer was aborted, e.g. because the device did not acknowledge")]
+    TransferAborted,
+    #[error("transfer is too long")]
+    TransferTooLong,
+    #[error("blocking transfer task failed")]
+    TaskFailed,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl I2cError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(I2cError::ResourceAlreadyAllocated),
+            a => Err(I2cError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+impl embedded_hal::i2c::Error for I2cError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            I2cError::TransferAborted => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// The roboRIO's I2C ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cPort {
+    Onboard,
+    Mxp,
+}
+
+impl From<I2cPort> for wpihal_sys::HAL_I2CPort {
+    fn from(value: I2cPort) -> Self {
+        match value {
+            I2cPort::Onboard => wpihal_sys::HAL_I2CPort_HAL_I2C_kOnboard,
+            I2cPort::Mxp => wpihal_sys::HAL_I2CPort_HAL_I2C_kMXP,
+        }
+    }
+}
+
+/// The pins of an I2C port, which are consumed by an [`I2c`] bus.
+pub trait I2cPins: crate::Sealed {
+    const PORT: I2cPort;
+}
+
+/// The pins of the onboard I2C port.
+pub struct OnboardI2cPins {
+    _private: (),
+}
+
+static ONBOARD_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl OnboardI2cPins {
+    pub fn take() -> Option<Self> {
+        let previously_taken = ONBOARD_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self { _private: () })
+        }
+    }
+}
+
+impl crate::Sealed for OnboardI2cPins {}
+impl I2cPins for OnboardI2cPins {
+    const PORT: I2cPort = I2cPort::Onboard;
+}
+
+/// The pins of the MXP I2C port: SCL on MXP pin 24, and SDA on MXP pin 25.
+pub type MxpI2cPins = (Dio24, Dio25);
+
+impl crate::Sealed for MxpI2cPins {}
+impl I2cPins for MxpI2cPins {
+    const PORT: I2cPort = I2cPort::Mxp;
+}
+
+fn hal_len(len: usize) -> Result<i32, I2cError> {
+    i32::try_from(len).map_err(|_| I2cError::TransferTooLong)
+}
+
+fn write(port: I2cPort, address: u8, write: &[u8]) -> Result<(), I2cError> {
+    let result = unsafe {
+        wpihal_sys::HAL_WriteI2C(
+            port.into(),
+            address.into(),
+            write.as_ptr(),
+            hal_len(write.len())?,
+        )
+    };
+    if result < 0 {
+        Err(I2cError::TransferAborted)
+    } else {
+        Ok(())
+    }
+}
+
+fn read(port: I2cPort, address: u8, read: &mut [u8]) -> Result<(), I2cError> {
+    let result = unsafe {
+        wpihal_sys::HAL_ReadI2C(
+            port.into(),
+            address.into(),
+            read.as_mut_ptr(),
+            hal_len(read.len())?,
+        )
+    };
+    if result < 0 {
+        Err(I2cError::TransferAborted)
+    } else {
+        Ok(())
+    }
+}
+
+fn write_read(port: I2cPort, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+    let result = unsafe {
+        wpihal_sys::HAL_TransactionI2C(
+            port.into(),
+            address.into(),
+            write.as_ptr(),
+            hal_len(write.len())?,
+            read.as_mut_ptr(),
+            hal_len(read.len())?,
+        )
+    };
+    if result < 0 {
+        Err(I2cError::TransferAborted)
+    } else {
+        Ok(())
+    }
+}
+
+/// A run of adjacent operations of the same kind, which embedded-hal requires to be
+/// transferred without a restart between them.
+enum Run {
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+}
+
+/// Merges adjacent operations of the same kind into owned buffers.
+fn runs(operations: &[Operation<'_>]) -> Vec<Run> {
+    let mut runs = Vec::new();
+    for operation in operations {
+        match (operation, runs.last_mut()) {
+            (Operation::Write(bytes), Some(Run::Write(buffer))) => buffer.extend_from_slice(bytes),
+            (Operation::Read(bytes), Some(Run::Read(buffer))) => {
+                buffer.resize(buffer.len() + bytes.len(), 0)
+            }
+            (Operation::Write(bytes), _) => runs.push(Run::Write(bytes.to_vec())),
+            (Operation::Read(bytes), _) => runs.push(Run::Read(vec![0; bytes.len()])),
+        }
+    }
+    runs
+}
+
+/// Transfers the runs, using a write-read transaction where a write is followed by a read.
+fn transfer(port: I2cPort, address: u8, runs: &mut [Run]) -> Result<(), I2cError> {
+    let mut i = 0;
+    while i < runs.len() {
+        match runs.split_at_mut(i + 1) {
+            ([.., Run::Write(w)], [Run::Read(r), ..]) => {
+                write_read(port, address, w, r)?;
+                i += 2;
+            }
+            ([.., Run::Write(w)], _) => {
+                write(port, address, w)?;
+                i += 1;
+            }
+            ([.., Run::Read(r)], _) => {
+                read(port, address, r)?;
+                i += 1;
+            }
+            ([], _) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Copies data read into the runs back into the read operations.
+fn copy_reads(runs: &[Run], operations: &mut [Operation<'_>]) {
+    let mut reads = runs.iter().filter_map(|run| match run {
+        Run::Read(buffer) => Some(buffer.as_slice()),
+        Run::Write(_) => None,
+    });
+    let mut current: &[u8] = &[];
+    for operation in operations {
+        if let Operation::Read(bytes) = operation {
+            if current.is_empty() {
+                current = reads.next().unwrap_or_default();
+            }
+            let (head, tail) = current.split_at(bytes.len().min(current.len()));
+            bytes[..head.len()].copy_from_slice(head);
+            current = tail;
+        }
+    }
+}
+
+/// An I2C bus on one of the roboRIO's I2C ports.
+pub struct I2c<P: I2cPins> {
+    pins: P,
+}
+
+impl<P: I2cPins> I2c<P> {
+    /// Opens the I2C port.
+    /// # Errors
+    /// Returns an error if wpihal fails to initialize the port. The pins are returned
+    /// with the error.
+    pub fn new(pins: P) -> Result<Self, (I2cError, P)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_InitializeI2C(P::PORT.into(), std::ptr::from_mut(&mut status)) };
+        match I2cError::from_status(status) {
+            Ok(()) => Ok(Self { pins }),
+            Err(e) => Err((e, pins)),
+        }
+    }
+
+    /// Gets the port this bus is on.
+    #[must_use]
+    pub fn port(&self) -> I2cPort {
+        P::PORT
+    }
+
+    /// Closes the I2C port, returning its pins.
+    #[must_use]
+    pub fn into_pins(self) -> P {
+        let this = ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_CloseI2C(P::PORT.into()) };
+        // SAFETY: `this` is never dropped, so the pins are moved out exactly once.
+        unsafe { std::ptr::read(&this.pins) }
+    }
+}
+
+impl<P: I2cPins> Drop for I2c<P> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_CloseI2C(P::PORT.into()) };
+    }
+}
+
+impl<P: I2cPins> ErrorType for I2c<P> {
+    type Error = I2cError;
+}
+
+impl<P: I2cPins> embedded_hal::i2c::I2c<SevenBitAddress> for I2c<P> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut runs = runs(operations);
+        transfer(P::PORT, address, &mut runs)?;
+        copy_reads(&runs, operations);
+        Ok(())
+    }
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        write(P::PORT, address, bytes)
+    }
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        read(P::PORT, address, buffer)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        write_read(P::PORT, address, bytes, buffer)
+    }
+}
+
+/// Transfers are blocking in wpihal, so they're run on tokio's blocking thread pool.
+/// The operations are copied into owned buffers first, so that dropping the future
+/// mid-transfer is safe.
+impl<P: I2cPins> embedded_hal_async::i2c::I2c<SevenBitAddress> for I2c<P> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let runs = runs(operations);
+        let runs = tokio::task::spawn_blocking(move || {
+            let mut runs = runs;
+            transfer(P::PORT, address, &mut runs).map(|()| runs)
+        })
+        .await
+        .map_err(|_| I2cError::TaskFailed)??;
+        copy_reads(&runs, operations);
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! Addressable LED strips, e.g. WS2812 strips.
+//!
+//! The FPGA can drive one strip of up to 5460 LEDs from a PWM header. An [`AddressableLed`]
+//! holds a buffer of colors, which is written to the strip by [`AddressableLed::update`].
+//! The [`pattern`] module provides animated patterns, which can be mapped onto segments
+//! of a strip with a [`PatternMap`].
+
+#![allow(clippy::module_name_repetitions)]
+
+pub mod pattern;
+pub use pattern::*;
+
+use thiserror::Error;
+use uom::si::{
+    f64::Time,
+    time::{microsecond, nanosecond},
+};
+
+use crate::error::HalError;
+use crate::pwm::{PwmError, PwmHeader, PwmOutput};
+
+#[derive(Error, Debug)]
+pub enum LedError {
+    #[error("the addressable LED output is already in use")]
+    NoAvailableLeds,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Pwm(#[from] PwmError),
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl LedError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::NO_AVAILABLE_RESOURCES => Err(LedError::NoAvailableLeds),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(LedError::ParameterOutOfRange),
+            a => Err(LedError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+/// The maximum number of LEDs in a strip
+pub const MAX_LENGTH: usize = wpihal_sys::HAL_kAddressableLEDMaxLength as usize;
+
+/// The color of one LED.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    pub const WHITE: Self = Self::new(255, 255, 255);
+    pub const RED: Self = Self::new(255, 0, 0);
+    pub const GREEN: Self = Self::new(0, 255, 0);
+    pub const BLUE: Self = Self::new(0, 0, 255);
+    pub const YELLOW: Self = Self::new(255, 255, 0);
+    pub const ORANGE: Self = Self::new(255, 165, 0);
+    pub const PURPLE: Self = Self::new(128, 0, 128);
+
+    #[must_use]
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Creates a color from a hue in degrees, and a saturation and value from 0 to 1.
+    #[must_use]
+    pub fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue {
+            h if h < 1.0 => (chroma, x, 0.0),
+            h if h < 2.0 => (x, chroma, 0.0),
+            h if h < 3.0 => (0.0, chroma, x),
+            h if h < 4.0 => (0.0, x, chroma),
+            h if h < 5.0 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let scale = |c: f64| ((c + m) * 255.0).round() as u8;
+        Self::new(scale(r), scale(g), scale(b))
+    }
+
+    /// Scales the brightness of the color by a factor from 0 to 1.
+    #[must_use]
+    pub fn dimmed(self, brightness: f64) -> Self {
+        let brightness = brightness.clamp(0.0, 1.0);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let scale = |c: u8| (f64::from(c) * brightness).round() as u8;
+        Self::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+}
+
+impl From<Rgb> for wpihal_sys::HAL_AddressableLEDData {
+    fn from(value: Rgb) -> Self {
+        Self {
+            b: value.b,
+            g: value.g,
+            r: value.r,
+            padding: 0,
+        }
+    }
+}
+
+fn nanoseconds(time: Time) -> i32 {
+    #[allow(clippy::cast_possible_truncation)]
+    let ns = time.get::<nanosecond>().round() as i32;
+    ns
+}
+
+/// An addressable LED strip on PWM header N. Colors are set in a buffer, and written to
+/// the strip on each [`AddressableLed::update`]. The MXP PWM pins can't drive LEDs.
+pub struct AddressableLed<const N: u8> {
+    handle: wpihal_sys::HAL_AddressableLEDHandle,
+    // the LED output is driven through the PWM output, so must be freed first
+    pwm: PwmOutput<PwmHeader<N>>,
+    buffer: Vec<Rgb>,
+    data: Vec<wpihal_sys::HAL_AddressableLEDData>,
+}
+
+impl<const N: u8> AddressableLed<N> {
+    /// Creates an LED strip with the given number of LEDs, all off. The strip isn't
+    /// driven until [`AddressableLed::start`] is called.
+    /// # Errors
+    /// Returns [`LedError::NoAvailableLeds`] if another LED strip already exists,
+    /// or [`LedError::ParameterOutOfRange`] if the length is more than [`MAX_LENGTH`].
+    /// The PWM header is returned with the error.
+    pub fn new(channel: PwmHeader<N>, length: usize) -> Result<Self, (LedError, PwmHeader<N>)> {
+        let pwm = PwmOutput::new(channel).map_err(|(e, channel)| (e.into(), channel))?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializeAddressableLED(pwm.handle(), std::ptr::from_mut(&mut status))
+        };
+        if let Err(e) = LedError::from_status(status) {
+            return Err((e, pwm.into_channel()));
+        }
+        let mut led = Self {
+            handle,
+            pwm,
+            buffer: Vec::new(),
+            data: Vec::new(),
+        };
+        match led.set_length(length) {
+            Ok(()) => Ok(led),
+            Err(e) => Err((e, led.into_channel())),
+        }
+    }
+
+    /// Gets the number of LEDs in the strip.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns true if the strip has no LEDs.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Sets the number of LEDs in the strip. New LEDs are turned off.
+    /// This is an expensive operation, so it shouldn't be done periodically.
+    /// # Errors
+    /// Returns [`LedError::ParameterOutOfRange`] if the length is more than [`MAX_LENGTH`].
+    pub fn set_length(&mut self, length: usize) -> Result<(), LedError> {
+        let hal_length = i32::try_from(length).map_err(|_| LedError::ParameterOutOfRange)?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAddressableLEDLength(
+                self.handle,
+                hal_length,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        LedError::from_status(status)?;
+        self.buffer.resize(length, Rgb::BLACK);
+        self.data.resize(length, Rgb::BLACK.into());
+        Ok(())
+    }
+
+    /// Sets the timing of the bits sent to the strip. The defaults suit WS2812 strips:
+    /// 400 ns high and 900 ns low for a 0, and 900 ns high and 600 ns low for a 1.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the strip.
+    pub fn set_bit_timing(
+        &mut self,
+        high_time_0: Time,
+        low_time_0: Time,
+        high_time_1: Time,
+        low_time_1: Time,
+    ) -> Result<(), LedError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAddressableLEDBitTiming(
+                self.handle,
+                nanoseconds(high_time_0),
+                nanoseconds(low_time_0),
+                nanoseconds(high_time_1),
+                nanoseconds(low_time_1),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        LedError::from_status(status)
+    }
+
+    /// Sets how long the output is held low between writes of the buffer, which the strip
+    /// uses to tell when a new write starts. The default is 280 µs.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the strip.
+    pub fn set_sync_time(&mut self, sync_time: Time) -> Result<(), LedError> {
+        #[allow(clippy::cast_possible_truncation)]
+        let microseconds = sync_time.get::<microsecond>().round() as i32;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetAddressableLEDSyncTime(
+                self.handle,
+                microseconds,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        LedError::from_status(status)
+    }
+
+    /// Starts continuously writing the last updated colors to the strip.
+    /// # Errors
+    /// Returns an error if wpihal fails to start the output.
+    pub fn start(&mut self) -> Result<(), LedError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_StartAddressableLEDOutput(self.handle, std::ptr::from_mut(&mut status));
+        }
+        LedError::from_status(status)
+    }
+
+    /// Stops writing to the strip. The LEDs keep their last colors.
+    /// # Errors
+    /// Returns an error if wpihal fails to stop the output.
+    pub fn stop(&mut self) -> Result<(), LedError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_StopAddressableLEDOutput(self.handle, std::ptr::from_mut(&mut status));
+        }
+        LedError::from_status(status)
+    }
+
+    /// Gets the buffer of colors.
+    #[must_use]
+    pub fn buffer(&self) -> &[Rgb] {
+        &self.buffer
+    }
+
+    /// Gets the buffer of colors for modification. Changes are written to the strip
+    /// on the next [`AddressableLed::update`].
+    #[must_use]
+    pub fn buffer_mut(&mut self) -> &mut [Rgb] {
+        &mut self.buffer
+    }
+
+    /// Writes the buffer of colors to the strip.
+    /// # Errors
+    /// Returns an error if wpihal fails to write the colors.
+    pub fn update(&mut self) -> Result<(), LedError> {
+        for (data, color) in self.data.iter_mut().zip(&self.buffer) {
+            *data = (*color).into();
+        }
+        // the length was checked to fit in an i32 by set_length
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let length = self.data.len() as i32;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_WriteAddressableLEDData(
+                self.handle,
+                self.data.as_ptr(),
+                length,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        LedError::from_status(status)
+    }
+
+    /// Renders a pattern map into the buffer and writes it to the strip.
+    /// # Errors
+    /// Returns an error if wpihal fails to write the colors.
+    pub fn update_with(&mut self, patterns: &PatternMap) -> Result<(), LedError> {
+        patterns.apply(&mut self.buffer);
+        self.update()
+    }
+
+    /// Frees the LED strip, returning its PWM header.
+    #[must_use]
+    pub fn into_channel(self) -> PwmHeader<N> {
+        let this = std::mem::ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_FreeAddressableLED(this.handle) };
+        // SAFETY: `this` is never dropped, so each field is moved out exactly once.
+        let (pwm, buffer, data) = unsafe {
+            (
+                std::ptr::read(&this.pwm),
+                std::ptr::read(&this.buffer),
+                std::ptr::read(&this.data),
+            )
+        };
+        drop((buffer, data));
+        pwm.into_channel()
+    }
+}
+
+impl<const N: u8> Drop for AddressableLed<N> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_FreeAddressableLED(self.handle) };
+    }
+}
@@ -0,0 +1,191 @@
+//! Patterns which can be rendered onto an LED strip, or a segment of one.
+//!
+//! A [`Pattern`] fills a slice of colors given the time since the animation started.
+//! Patterns can be wrapped by others, e.g. a [`Scroll`] of a [`Rainbow`], and are
+//! mapped to segments of a strip with a [`PatternMap`].
+
+use std::{ops::Range, time::Instant};
+
+use uom::si::{
+    f64::{Frequency, Time},
+    frequency::hertz,
+    time::second,
+};
+
+use super::Rgb;
+
+/// A pattern of colors, which may be animated.
+pub trait Pattern {
+    /// Fills `pixels` with the pattern, `elapsed` after the start of the animation.
+    fn apply(&self, elapsed: Time, pixels: &mut [Rgb]);
+}
+
+impl<P: Pattern + ?Sized> Pattern for Box<P> {
+    fn apply(&self, elapsed: Time, pixels: &mut [Rgb]) {
+        (**self).apply(elapsed, pixels);
+    }
+}
+
+/// Every LED is the same color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solid(pub Rgb);
+
+impl Pattern for Solid {
+    fn apply(&self, _elapsed: Time, pixels: &mut [Rgb]) {
+        pixels.fill(self.0);
+    }
+}
+
+/// Alternates between a pattern and all LEDs off.
+pub struct Blink<P: Pattern> {
+    pub pattern: P,
+    pub on_time: Time,
+    pub off_time: Time,
+}
+
+impl<P: Pattern> Pattern for Blink<P> {
+    fn apply(&self, elapsed: Time, pixels: &mut [Rgb]) {
+        let period = (self.on_time + self.off_time).get::<second>();
+        let phase = elapsed.get::<second>().rem_euclid(period);
+        if phase < self.on_time.get::<second>() {
+            self.pattern.apply(elapsed, pixels);
+        } else {
+            pixels.fill(Rgb::BLACK);
+        }
+    }
+}
+
+/// A full cycle of hues across the LEDs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rainbow {
+    /// Saturation from 0 to 1
+    pub saturation: f64,
+    /// Value (brightness) from 0 to 1
+    pub value: f64,
+}
+
+impl Default for Rainbow {
+    fn default() -> Self {
+        Self {
+            saturation: 1.0,
+            value: 1.0,
+        }
+    }
+}
+
+impl Pattern for Rainbow {
+    fn apply(&self, _elapsed: Time, pixels: &mut [Rgb]) {
+        #[allow(clippy::cast_precision_loss)]
+        let len = pixels.len() as f64;
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let hue = i as f64 / len * 360.0;
+            *pixel = Rgb::from_hsv(hue, self.saturation, self.value);
+        }
+    }
+}
+
+/// Lights a fraction of the LEDs from the start of the segment, e.g. to show
+/// how far a mechanism is from its target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressBar {
+    pub color: Rgb,
+    pub background: Rgb,
+    /// Fraction of LEDs lit, from 0 to 1
+    pub progress: f64,
+}
+
+impl Pattern for ProgressBar {
+    fn apply(&self, _elapsed: Time, pixels: &mut [Rgb]) {
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let lit = (self.progress.clamp(0.0, 1.0) * pixels.len() as f64).round() as usize;
+        let (lit, unlit) = pixels.split_at_mut(lit);
+        lit.fill(self.color);
+        unlit.fill(self.background);
+    }
+}
+
+/// Moves a pattern along the LEDs, wrapping around at the end of the segment.
+/// A negative speed scrolls towards the start of the segment.
+pub struct Scroll<P: Pattern> {
+    pub pattern: P,
+    /// LEDs moved per second
+    pub speed: Frequency,
+}
+
+impl<P: Pattern> Pattern for Scroll<P> {
+    fn apply(&self, elapsed: Time, pixels: &mut [Rgb]) {
+        self.pattern.apply(elapsed, pixels);
+        if pixels.is_empty() {
+            return;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let len = pixels.len() as f64;
+        let offset = (self.speed.get::<hertz>() * elapsed.get::<second>())
+            .floor()
+            .rem_euclid(len);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        pixels.rotate_right(offset as usize);
+    }
+}
+
+/// Maps patterns to segments of an LED strip. Segments are applied in the order they
+/// were set, so later segments cover earlier ones where they overlap. LEDs not covered
+/// by any segment are left unchanged.
+pub struct PatternMap {
+    segments: Vec<(Range<usize>, Box<dyn Pattern + Send>)>,
+    start: Instant,
+}
+
+impl PatternMap {
+    /// Creates an empty map. Animations are timed from when the map was created.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            segments: Vec::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Sets the pattern for a segment, replacing any pattern set for exactly the same segment.
+    pub fn set(&mut self, segment: Range<usize>, pattern: impl Pattern + Send + 'static) {
+        let pattern = Box::new(pattern);
+        if let Some(existing) = self.segments.iter_mut().find(|(s, _)| *s == segment) {
+            existing.1 = pattern;
+        } else {
+            self.segments.push((segment, pattern));
+        }
+    }
+
+    /// Removes the pattern for a segment.
+    pub fn clear(&mut self, segment: &Range<usize>) {
+        self.segments.retain(|(s, _)| s != segment);
+    }
+
+    /// Restarts the animations of all patterns.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    /// Renders every segment into a buffer of colors. Segments which extend past
+    /// the end of the buffer are cut short.
+    pub fn apply(&self, pixels: &mut [Rgb]) {
+        let elapsed = Time::new::<second>(self.start.elapsed().as_secs_f64());
+        for (segment, pattern) in &self.segments {
+            let end = segment.end.min(pixels.len());
+            if let Some(pixels) = pixels.get_mut(segment.start..end) {
+                pattern.apply(elapsed, pixels);
+            }
+        }
+    }
+}
+
+impl Default for PatternMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
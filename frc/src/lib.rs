@@ -1,7 +1,20 @@
+pub mod accelerometer;
+pub mod analog;
+pub mod counter;
 pub mod dio;
+pub mod duty_cycle;
+pub mod encoder;
 pub mod error;
+pub mod gyro;
+pub mod i2c;
+pub mod led;
+pub mod motor;
 pub mod pneumatics;
+pub mod pwm;
 pub mod reactor;
+pub mod relay;
+pub mod safety;
+pub mod spi;
 
 pub use uom;
 
@@ -0,0 +1,153 @@
+#![allow(clippy::module_name_repetitions)]
+
+use uom::si::{electric_potential::volt, f64::ElectricPotential};
+
+/// The voltage assumed by [`MotorController::set_voltage`] if the battery voltage can't be read
+const NOMINAL_BATTERY_VOLTAGE: f64 = 12.0;
+
+/// Gets the input voltage of the roboRIO, which is the battery voltage less any losses in wiring.
+#[must_use]
+fn battery_voltage() -> ElectricPotential {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let voltage = unsafe { wpihal_sys::HAL_GetVinVoltage(std::ptr::from_mut(&mut status)) };
+    if status == wpihal_sys::HAL_SUCCESS && voltage > 0.0 {
+        ElectricPotential::new::<volt>(voltage)
+    } else {
+        ElectricPotential::new::<volt>(NOMINAL_BATTERY_VOLTAGE)
+    }
+}
+
+/// A motor controller, which drives a motor with a duty cycle from -1 (full reverse)
+/// to 1 (full forward). Implemented by all motor controller types in this module.
+/// Generic code should prefer to use either this trait or the [`AnyMotorController`] struct.
+pub trait MotorController {
+    /// Sets the output of the motor controller as a duty cycle from -1 to 1.
+    /// If the controller is inverted, the output is negated.
+    fn set(&mut self, output: f64);
+
+    /// Gets the most recently set duty cycle, from -1 to 1.
+    #[must_use]
+    fn get(&self) -> f64;
+
+    /// Sets the output of the motor controller as a voltage. This is scaled by the
+    /// current battery voltage, so the output is consistent as the battery drains.
+    fn set_voltage(&mut self, voltage: ElectricPotential) {
+        self.set((voltage / battery_voltage()).value);
+    }
+
+    /// Stops the motor until the output is next set.
+    fn stop(&mut self) {
+        self.set(0.0);
+    }
+
+    /// Sets whether the output of the motor controller is negated.
+    fn set_inverted(&mut self, inverted: bool);
+
+    /// Gets whether the output of the motor controller is negated.
+    #[must_use]
+    fn inverted(&self) -> bool;
+}
+
+/// A motor controller with its type erased, e.g. for homogeneous storage of
+/// several different motor controllers in a slice or array.
+pub struct AnyMotorController {
+    controller: Box<dyn MotorController + Send + 'static>,
+}
+
+impl AnyMotorController {
+    /// Type-erases a motor controller.
+    #[must_use]
+    pub fn new(controller: impl MotorController + Send + 'static) -> Self {
+        Self {
+            controller: Box::new(controller),
+        }
+    }
+}
+
+impl MotorController for AnyMotorController {
+    fn set(&mut self, output: f64) {
+        self.controller.set(output);
+    }
+
+    fn get(&self) -> f64 {
+        self.controller.get()
+    }
+
+    fn set_voltage(&mut self, voltage: ElectricPotential) {
+        self.controller.set_voltage(voltage);
+    }
+
+    fn stop(&mut self) {
+        self.controller.stop();
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.controller.set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        self.controller.inverted()
+    }
+}
+
+/// Several motor controllers driven in lockstep, e.g. the motors on one side of a drivetrain.
+/// Each controller keeps its own inversion, and the group can be inverted as a whole.
+/// To group motor controllers of different types, use [`AnyMotorController`].
+pub struct MotorGroup<M: MotorController = AnyMotorController> {
+    controllers: Vec<M>,
+    inverted: bool,
+    output: f64,
+}
+
+impl<M: MotorController> MotorGroup<M> {
+    /// Creates a group of motor controllers.
+    #[must_use]
+    pub fn new(controllers: impl IntoIterator<Item = M>) -> Self {
+        Self {
+            controllers: controllers.into_iter().collect(),
+            inverted: false,
+            output: 0.0,
+        }
+    }
+
+    /// Gets the motor controllers in the group.
+    #[must_use]
+    pub fn controllers(&self) -> &[M] {
+        &self.controllers
+    }
+
+    /// Releases the motor controllers in the group.
+    #[must_use]
+    pub fn into_controllers(self) -> Vec<M> {
+        self.controllers
+    }
+}
+
+impl<M: MotorController> MotorController for MotorGroup<M> {
+    fn set(&mut self, output: f64) {
+        self.output = output;
+        let output = if self.inverted { -output } else { output };
+        for controller in &mut self.controllers {
+            controller.set(output);
+        }
+    }
+
+    fn get(&self) -> f64 {
+        self.output
+    }
+
+    fn stop(&mut self) {
+        self.output = 0.0;
+        for controller in &mut self.controllers {
+            controller.stop();
+        }
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
@@ -0,0 +1,14 @@
+//! # Motor Controllers
+//!
+//! This module defines a common interface for motor controllers, [`MotorController`], so that
+//! drivetrain and mechanism code can be written once regardless of how the controller is
+//! connected. PWM motor controllers are supported by [`PwmMotorController`]; CAN motor
+//! controllers can implement the trait as well.
+//!
+//! Several motor controllers of different types can be stored homogeneously by type-erasing
+//! them into [`AnyMotorController`]s, and driven together as a [`MotorGroup`].
+
+pub mod controller;
+pub use controller::*;
+pub mod pwm;
+pub use pwm::*;
@@ -0,0 +1,61 @@
+#![allow(clippy::module_name_repetitions)]
+
+use crate::pwm::{PwmChannel, PwmConfig, PwmError, PwmOutput};
+
+use super::MotorController;
+
+/// A motor controller connected to a PWM output, such as a Spark or Victor SP.
+pub struct PwmMotorController<C: PwmChannel> {
+    pwm: PwmOutput<C>,
+    inverted: bool,
+}
+
+impl<C: PwmChannel> PwmMotorController<C> {
+    /// Creates a motor controller on a PWM channel with the given pulse bounds,
+    /// such as [`PwmConfig::SPARK`].
+    /// # Errors
+    /// Returns [`PwmError::ResourceAlreadyAllocated`] if the channel is already in use.
+    /// The channel is returned with the error.
+    pub fn new(channel: C, config: PwmConfig) -> Result<Self, (PwmError, C)> {
+        Ok(Self {
+            pwm: PwmOutput::motor_controller(channel, config)?,
+            inverted: false,
+        })
+    }
+
+    /// Releases the PWM output used by this motor controller.
+    #[must_use]
+    pub fn into_pwm(self) -> PwmOutput<C> {
+        self.pwm
+    }
+}
+
+// Setting a PWM output only fails if its handle is invalid, which can't happen for an
+// output owned by this type, so errors are ignored.
+impl<C: PwmChannel> MotorController for PwmMotorController<C> {
+    fn set(&mut self, output: f64) {
+        let output = if self.inverted { -output } else { output };
+        let _ = self.pwm.set_speed(output);
+    }
+
+    fn get(&self) -> f64 {
+        let output = self.pwm.speed().unwrap_or(0.0);
+        if self.inverted {
+            -output
+        } else {
+            output
+        }
+    }
+
+    fn stop(&mut self) {
+        let _ = self.pwm.set_disabled();
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    fn inverted(&self) -> bool {
+        self.inverted
+    }
+}
@@ -0,0 +1,373 @@
+//! PWM outputs, e.g. for PWM motor controllers and servos.
+//!
+//! The roboRIO has 10 PWM headers, taken through [`PwmPort`], and 10 more PWM channels on
+//! MXP pins which support it (see [`MxpPwmPin`]). Either kind of channel is turned into a
+//! [`PwmOutput`], which returns the channel when released with [`PwmOutput::into_channel`].
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use thiserror::Error;
+use uom::si::{f64::Time, time::microsecond};
+
+use crate::dio::MxpPwmPin;
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum PwmError {
+    #[error("channel is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("channel is out of range")]
+    OutOfRange,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl PwmError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(PwmError::ResourceAlreadyAllocated),
+            wpihal_sys::RESOURCE_OUT_OF_RANGE => Err(PwmError::OutOfRange),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(PwmError::ParameterOutOfRange),
+            a => Err(PwmError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+/// A PWM channel which can be turned into a [`PwmOutput`]: a PWM header, or an MXP pin.
+pub trait PwmChannel: crate::Sealed {
+    /// The wpihal PWM channel number.
+    fn pwm_channel(&self) -> u8;
+}
+
+/// Represents one of the 10 onboard PWM headers.
+/// N is the header number, which is also the wpihal PWM channel number.
+pub struct PwmHeader<const N: u8> {
+    _private: (),
+}
+
+impl<const N: u8> PwmHeader<N> {
+    const _VALID: () = assert!(N < 10);
+
+    fn new() -> Self {
+        let () = Self::_VALID;
+        Self { _private: () }
+    }
+}
+
+impl<const N: u8> crate::Sealed for PwmHeader<N> {}
+
+impl<const N: u8> PwmChannel for PwmHeader<N> {
+    fn pwm_channel(&self) -> u8 {
+        N
+    }
+}
+
+impl<P: MxpPwmPin> PwmChannel for P {
+    fn pwm_channel(&self) -> u8 {
+        P::PWM_CHANNEL
+    }
+}
+
+pub type Pwm0 = PwmHeader<0>;
+pub type Pwm1 = PwmHeader<1>;
+pub type Pwm2 = PwmHeader<2>;
+pub type Pwm3 = PwmHeader<3>;
+pub type Pwm4 = PwmHeader<4>;
+pub type Pwm5 = PwmHeader<5>;
+pub type Pwm6 = PwmHeader<6>;
+pub type Pwm7 = PwmHeader<7>;
+pub type Pwm8 = PwmHeader<8>;
+pub type Pwm9 = PwmHeader<9>;
+
+pub struct PwmPort {
+    pub pwm0: Pwm0,
+    pub pwm1: Pwm1,
+    pub pwm2: Pwm2,
+    pub pwm3: Pwm3,
+    pub pwm4: Pwm4,
+    pub pwm5: Pwm5,
+    pub pwm6: Pwm6,
+    pub pwm7: Pwm7,
+    pub pwm8: Pwm8,
+    pub pwm9: Pwm9,
+}
+
+static PORT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl PwmPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = PORT_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                pwm0: Pwm0::new(),
+                pwm1: Pwm1::new(),
+                pwm2: Pwm2::new(),
+                pwm3: Pwm3::new(),
+                pwm4: Pwm4::new(),
+                pwm5: Pwm5::new(),
+                pwm6: Pwm6::new(),
+                pwm7: Pwm7::new(),
+                pwm8: Pwm8::new(),
+                pwm9: Pwm9::new(),
+            })
+        }
+    }
+}
+
+/// Pulse bounds of a PWM output, in microseconds. Speeds from -1 to 1 are scaled between
+/// `min` and `max`, and speeds within the deadband are output as `center`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PwmConfig {
+    pub max: i32,
+    pub deadband_max: i32,
+    pub center: i32,
+    pub deadband_min: i32,
+    pub min: i32,
+}
+
+impl PwmConfig {
+    /// REV Robotics SPARK
+    pub const SPARK: Self = Self::new(2003, 1550, 1500, 1460, 999);
+    /// REV Robotics SPARK MAX, in PWM mode
+    pub const SPARK_MAX: Self = Self::new(2003, 1550, 1500, 1460, 999);
+    /// CTRE Talon SR
+    pub const TALON_SR: Self = Self::new(2037, 1539, 1513, 1487, 989);
+    /// VEX Robotics Victor SP
+    pub const VICTOR_SP: Self = Self::new(2004, 1520, 1500, 1480, 997);
+
+    #[must_use]
+    pub const fn new(
+        max: i32,
+        deadband_max: i32,
+        center: i32,
+        deadband_min: i32,
+        min: i32,
+    ) -> Self {
+        Self {
+            max,
+            deadband_max,
+            center,
+            deadband_min,
+            min,
+        }
+    }
+}
+
+/// How often a PWM pulse is output, as a multiple of the base period of about 5 ms.
+/// Older devices like servos may need a longer period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeriodMultiplier {
+    X1,
+    X2,
+    X4,
+}
+
+/// A PWM output on a [`PwmChannel`].
+pub struct PwmOutput<C: PwmChannel> {
+    handle: wpihal_sys::HAL_DigitalHandle,
+    channel: C,
+}
+
+impl<C: PwmChannel> PwmOutput<C> {
+    /// Creates a PWM output on the given channel. The output is disabled until a
+    /// speed, position or pulse time is set.
+    /// # Errors
+    /// Returns [`PwmError::ResourceAlreadyAllocated`] if the channel is already in use.
+    /// The channel is returned with the error.
+    pub fn new(channel: C) -> Result<Self, (PwmError, C)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let handle = unsafe {
+            wpihal_sys::HAL_InitializePWMPort(
+                wpihal_sys::HAL_GetPort(channel.pwm_channel().into()),
+                c"".as_ptr(),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        match PwmError::from_status(status) {
+            Ok(()) => Ok(Self { handle, channel }),
+            Err(e) => Err((e, channel)),
+        }
+    }
+
+    /// Creates a PWM output for a motor controller with the given pulse bounds, such as
+    /// [`PwmConfig::SPARK`]. The output is set to neutral.
+    /// # Errors
+    /// Returns [`PwmError::ResourceAlreadyAllocated`] if the channel is already in use.
+    /// The channel is returned with the error.
+    pub fn motor_controller(channel: C, config: PwmConfig) -> Result<Self, (PwmError, C)> {
+        let mut pwm = Self::new(channel)?;
+        let configured = pwm
+            .set_config(config)
+            .and_then(|()| pwm.set_period_multiplier(PeriodMultiplier::X1))
+            .and_then(|()| pwm.set_speed(0.0))
+            .and_then(|()| pwm.latch_zero());
+        match configured {
+            Ok(()) => Ok(pwm),
+            Err(e) => Err((e, pwm.into_channel())),
+        }
+    }
+
+    /// Gets the wpihal PWM channel number of this output.
+    #[must_use]
+    pub fn channel(&self) -> u8 {
+        self.channel.pwm_channel()
+    }
+
+    pub(crate) fn handle(&self) -> wpihal_sys::HAL_DigitalHandle {
+        self.handle
+    }
+
+    /// Sets the pulse bounds of the output.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the output.
+    pub fn set_config(&mut self, config: PwmConfig) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMConfigMicroseconds(
+                self.handle,
+                config.max,
+                config.deadband_max,
+                config.center,
+                config.deadband_min,
+                config.min,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        PwmError::from_status(status)
+    }
+
+    /// Sets whether speeds within the deadband are output as the center pulse.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the output.
+    pub fn set_eliminate_deadband(&mut self, eliminate: bool) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMEliminateDeadband(
+                self.handle,
+                i32::from(eliminate),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        PwmError::from_status(status)
+    }
+
+    /// Sets the output from -1 (the minimum pulse) to 1 (the maximum pulse).
+    /// # Errors
+    /// Returns an error if wpihal fails to set the output.
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_SetPWMSpeed(self.handle, speed, std::ptr::from_mut(&mut status)) };
+        PwmError::from_status(status)
+    }
+
+    /// Gets the output from -1 to 1.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the output.
+    pub fn speed(&self) -> Result<f64, PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let speed =
+            unsafe { wpihal_sys::HAL_GetPWMSpeed(self.handle, std::ptr::from_mut(&mut status)) };
+        PwmError::from_status(status)?;
+        Ok(speed)
+    }
+
+    /// Sets the output from 0 (the minimum pulse) to 1 (the maximum pulse), e.g. for a servo.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the output.
+    pub fn set_position(&mut self, position: f64) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMPosition(self.handle, position, std::ptr::from_mut(&mut status));
+        }
+        PwmError::from_status(status)
+    }
+
+    /// Sets the length of the output pulse directly.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the output.
+    pub fn set_pulse_time(&mut self, pulse_time: Time) -> Result<(), PwmError> {
+        #[allow(clippy::cast_possible_truncation)]
+        let microseconds = pulse_time.get::<microsecond>().round() as i32;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMPulseTimeMicroseconds(
+                self.handle,
+                microseconds,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        PwmError::from_status(status)
+    }
+
+    /// Stops outputting pulses. Motor controllers treat this as neutral.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the output.
+    pub fn set_disabled(&mut self) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_SetPWMDisabled(self.handle, std::ptr::from_mut(&mut status)) };
+        PwmError::from_status(status)
+    }
+
+    /// Sets how often a pulse is output.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the output.
+    pub fn set_period_multiplier(&mut self, multiplier: PeriodMultiplier) -> Result<(), PwmError> {
+        // the FPGA squelches (skips) pulses according to this mask
+        let squelch_mask = match multiplier {
+            PeriodMultiplier::X1 => 0,
+            PeriodMultiplier::X2 => 1,
+            PeriodMultiplier::X4 => 3,
+        };
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMPeriodScale(
+                self.handle,
+                squelch_mask,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        PwmError::from_status(status)
+    }
+
+    /// Briefly outputs no pulses, so that motor controllers latch their current output as zero.
+    /// # Errors
+    /// Returns an error if wpihal fails to set the output.
+    pub fn latch_zero(&mut self) -> Result<(), PwmError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_LatchPWMZero(self.handle, std::ptr::from_mut(&mut status)) };
+        PwmError::from_status(status)
+    }
+
+    /// Disables and frees the output, returning its channel.
+    #[must_use]
+    pub fn into_channel(self) -> C {
+        let this = ManuallyDrop::new(self);
+        this.free();
+        // SAFETY: `this` is never dropped, so the channel is moved out exactly once.
+        unsafe { std::ptr::read(&this.channel) }
+    }
+
+    fn free(&self) {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetPWMDisabled(self.handle, std::ptr::from_mut(&mut status));
+            wpihal_sys::HAL_FreePWMPort(self.handle, std::ptr::from_mut(&mut status));
+        }
+    }
+}
+
+impl<C: PwmChannel> Drop for PwmOutput<C> {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
@@ -1,5 +1,20 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    thread::Thread,
+};
+
+use futures::{task::AtomicWaker, Stream};
+use once_cell::sync::Lazy;
 use thiserror::Error;
-use tokio::sync::oneshot;
+use uom::si::{f64::Time, time::microsecond};
 
 use crate::error::HalError;
 
@@ -24,16 +39,250 @@ impl InterruptError {
     }
 }
 
+#[derive(Clone, Copy)]
 pub(crate) enum EdgeType {
     Rising,
     Falling,
     Either,
 }
 
-pub(crate) async fn wait_for_edge(
-    dio_handle: wpihal_sys::HAL_DigitalHandle,
-    edge: EdgeType,
-) -> Result<(), InterruptError> {
+/// The direction of a signal edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The signal went from low to high
+    Rising,
+    /// The signal went from high to low
+    Falling,
+}
+
+/// A signal edge on a digital input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeEvent {
+    /// The direction of the edge
+    pub edge: Edge,
+    /// The FPGA time at which the edge happened
+    pub timestamp: Time,
+}
+
+/// A signal which can be routed to an interrupt.
+#[derive(Clone, Copy)]
+pub(crate) struct Source {
+    handle: wpihal_sys::HAL_Handle,
+    trigger_type: wpihal_sys::HAL_AnalogTriggerType,
+}
+
+impl Source {
+    /// A DIO input pin
+    pub(crate) fn digital(handle: wpihal_sys::HAL_DigitalHandle) -> Self {
+        Self {
+            handle,
+            // ignored for DIO pins
+            trigger_type: wpihal_sys::HAL_AnalogTriggerType_HAL_Trigger_kInWindow,
+        }
+    }
+
+    /// One of the outputs of an analog trigger
+    pub(crate) fn analog_trigger(
+        handle: wpihal_sys::HAL_AnalogTriggerHandle,
+        trigger_type: wpihal_sys::HAL_AnalogTriggerType,
+    ) -> Self {
+        Self {
+            handle,
+            trigger_type,
+        }
+    }
+}
+
+/// How long the dispatcher waits for IRQs before picking up newly registered interrupts.
+/// IRQs stay latched until they are waited on, so no edges are missed in between.
+const DISPATCH_TIMEOUT: f64 = 0.02;
+
+static REACTOR: Lazy<Reactor> = Lazy::new(Reactor::new);
+
+/// Holds the interrupts registered for each digital source, and the dispatcher thread which
+/// waits on all of them at once.
+struct Reactor {
+    interrupts: Arc<Mutex<HashMap<wpihal_sys::HAL_Handle, Arc<Interrupt>>>>,
+    dispatcher: Thread,
+}
+
+impl Reactor {
+    fn new() -> Self {
+        let interrupts = Arc::new(Mutex::new(HashMap::new()));
+        let interrupts2 = Arc::clone(&interrupts);
+        let dispatcher = std::thread::spawn(move || dispatch(&interrupts2))
+            .thread()
+            .clone();
+        Self {
+            interrupts,
+            dispatcher,
+        }
+    }
+}
+
+fn dispatch(interrupts: &Mutex<HashMap<wpihal_sys::HAL_Handle, Arc<Interrupt>>>) {
+    loop {
+        let (waiter, mask) = {
+            let interrupts = interrupts.lock().unwrap();
+            let mask = interrupts
+                .values()
+                .fold(0, |mask, interrupt| mask | interrupt.mask());
+            // keep the waited-on interrupt alive until the wait returns, even if it's released
+            // in the meantime, as cleaning it up frees the IRQ context being waited on
+            let waiter = interrupts.values().next().map(Arc::clone);
+            (waiter, mask)
+        };
+        let Some(waiter) = waiter else {
+            // unparked when an interrupt is registered
+            std::thread::park();
+            continue;
+        };
+        // any registered handle will do, as the mask selects which IRQs to wait for
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let asserted = unsafe {
+            wpihal_sys::HAL_WaitForMultipleInterrupts(
+                waiter.irq_handle,
+                mask,
+                DISPATCH_TIMEOUT,
+                i32::from(false),
+                std::ptr::from_mut(&mut status),
+            )
+        };
+        drop(waiter);
+        if status != wpihal_sys::HAL_SUCCESS {
+            std::thread::sleep(std::time::Duration::from_secs_f64(DISPATCH_TIMEOUT));
+            continue;
+        }
+        if asserted != 0 {
+            for interrupt in interrupts.lock().unwrap().values() {
+                interrupt.dispatch(asserted);
+            }
+        }
+    }
+}
+
+/// Gets the interrupt registered for a source, registering one if there is none.
+/// The interrupt stays registered until [`release`] is called for the source's handle.
+pub(crate) fn interrupt(source: Source) -> Result<Arc<Interrupt>, InterruptError> {
+    let mut interrupts = REACTOR.interrupts.lock().unwrap();
+    if let Some(interrupt) = interrupts.get(&source.handle) {
+        return Ok(Arc::clone(interrupt));
+    }
+    let interrupt = Arc::new(Interrupt::new(source)?);
+    interrupts.insert(source.handle, Arc::clone(&interrupt));
+    REACTOR.dispatcher.unpark();
+    Ok(interrupt)
+}
+
+/// Frees the interrupt registered for a source, if any.
+/// This must be called before the source itself is freed. If the dispatcher is waiting on
+/// the interrupt, it's cleaned up once the wait returns, within [`DISPATCH_TIMEOUT`].
+pub(crate) fn release(source_handle: wpihal_sys::HAL_Handle) {
+    // don't start the reactor just to find out nothing is registered
+    if let Some(reactor) = Lazy::get(&REACTOR) {
+        let interrupt = reactor.interrupts.lock().unwrap().remove(&source_handle);
+        drop(interrupt);
+    }
+}
+
+/// An interrupt registered for a source, listening for both edges.
+/// The dispatcher thread counts the edges, so futures only need to compare the counts
+/// against a snapshot - dropping a future at any point can't lose or consume an edge.
+pub(crate) struct Interrupt {
+    irq_handle: wpihal_sys::HAL_InterruptHandle,
+    /// FPGA time at which the interrupt was registered. IRQs left latched by a previous
+    /// user of the same interrupt index are older than this, and are ignored.
+    registered_at: u64,
+    rising: AtomicU64,
+    falling: AtomicU64,
+    waker: AtomicWaker,
+    /// Edges waiting to be taken by an [`EdgeStream`], if one exists
+    events: Mutex<Option<VecDeque<Result<EdgeEvent, InterruptError>>>>,
+}
+
+impl Interrupt {
+    fn new(source: Source) -> Result<Self, InterruptError> {
+        let irq_handle = initialize_interrupt(source)?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let registered_at = unsafe { wpihal_sys::HAL_GetFPGATime(std::ptr::from_mut(&mut status)) };
+        let interrupt = Self {
+            irq_handle,
+            registered_at,
+            rising: AtomicU64::new(0),
+            falling: AtomicU64::new(0),
+            waker: AtomicWaker::new(),
+            events: Mutex::new(None),
+        };
+        InterruptError::from_status(status)?;
+        Ok(interrupt)
+    }
+
+    /// wpihal allocates interrupts from a pool of 8. The interrupt at index i
+    /// uses IRQ i for rising edges, and IRQ i + 8 for falling edges.
+    fn index(&self) -> i32 {
+        self.irq_handle & 0xFFFF
+    }
+
+    fn mask(&self) -> i64 {
+        (1 << self.index()) | (1 << (self.index() + 8))
+    }
+
+    fn counter(&self, edge: Edge) -> &AtomicU64 {
+        match edge {
+            Edge::Rising => &self.rising,
+            Edge::Falling => &self.falling,
+        }
+    }
+
+    /// Records the edges asserted for this interrupt and wakes its waiting task.
+    fn dispatch(&self, asserted: i64) {
+        let index = self.index();
+        let mut edges: Vec<(Edge, Result<u64, InterruptError>)> =
+            [(Edge::Rising, index), (Edge::Falling, index + 8)]
+                .into_iter()
+                .filter(|(_, irq)| asserted & (1 << irq) != 0)
+                .map(|(edge, _)| (edge, read_timestamp(self.irq_handle, edge)))
+                .filter(|(_, timestamp)| !matches!(timestamp, Ok(t) if *t < self.registered_at))
+                .collect();
+        if edges.is_empty() {
+            return;
+        }
+        edges.sort_by_key(|(_, timestamp)| timestamp.as_ref().ok().copied());
+        for (edge, _) in &edges {
+            self.counter(*edge).fetch_add(1, Ordering::Release);
+        }
+        if let Some(queue) = self.events.lock().unwrap().as_mut() {
+            queue.extend(edges.into_iter().map(|(edge, timestamp)| {
+                #[allow(clippy::cast_precision_loss)]
+                timestamp.map(|timestamp| EdgeEvent {
+                    edge,
+                    timestamp: Time::new::<microsecond>(timestamp as f64),
+                })
+            }));
+        }
+        self.waker.wake();
+    }
+
+    /// Returns a future which resolves on the next edge of the given type after this call.
+    pub(crate) fn edge(self: &Arc<Self>, edge: EdgeType) -> EdgeFuture {
+        EdgeFuture {
+            interrupt: Arc::clone(self),
+            edge,
+            rising: self.rising.load(Ordering::Acquire),
+            falling: self.falling.load(Ordering::Acquire),
+        }
+    }
+}
+
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_CleanInterrupts(self.irq_handle) };
+    }
+}
+
+/// Allocates an interrupt and routes a source to it, listening for both edges.
+/// The interrupt is cleaned up if any step fails.
+fn initialize_interrupt(source: Source) -> Result<wpihal_sys::HAL_InterruptHandle, InterruptError> {
     let mut status = wpihal_sys::HAL_SUCCESS;
     let irq_handle =
         unsafe { wpihal_sys::HAL_InitializeInterrupts(std::ptr::from_mut(&mut status)) };
@@ -41,50 +290,140 @@ pub(crate) async fn wait_for_edge(
     unsafe {
         wpihal_sys::HAL_RequestInterrupts(
             irq_handle,
-            dio_handle,
-            wpihal_sys::HAL_AnalogTriggerType_HAL_Trigger_kInWindow,
+            source.handle,
+            source.trigger_type,
             std::ptr::from_mut(&mut status),
         );
     };
-    InterruptError::from_status(status)?;
-    let (rising, falling) = match edge {
-        EdgeType::Rising => (true, false),
-        EdgeType::Falling => (false, true),
-        EdgeType::Either => (true, true),
-    };
-    unsafe {
-        wpihal_sys::HAL_SetInterruptUpSourceEdge(
-            irq_handle,
-            i32::from(rising),
-            i32::from(falling),
-            std::ptr::from_mut(&mut status),
-        );
+    if status == wpihal_sys::HAL_SUCCESS {
+        unsafe {
+            wpihal_sys::HAL_SetInterruptUpSourceEdge(
+                irq_handle,
+                i32::from(true),
+                i32::from(true),
+                std::ptr::from_mut(&mut status),
+            );
+        }
     }
-    InterruptError::from_status(status)?;
-    wait_for_blocking(|| loop {
-        let asserted = unsafe {
-            wpihal_sys::HAL_WaitForInterrupt(
+    if let Err(e) = InterruptError::from_status(status) {
+        unsafe { wpihal_sys::HAL_CleanInterrupts(irq_handle) };
+        return Err(e);
+    }
+    Ok(irq_handle)
+}
+
+/// Reads the FPGA time in microseconds of the most recent edge seen by an interrupt.
+fn read_timestamp(
+    irq_handle: wpihal_sys::HAL_InterruptHandle,
+    edge: Edge,
+) -> Result<u64, InterruptError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let lower = unsafe {
+        match edge {
+            Edge::Rising => wpihal_sys::HAL_ReadInterruptRisingTimestamp(
                 irq_handle,
-                10.0,
-                i32::from(false),
                 std::ptr::from_mut(&mut status),
-            )
+            ),
+            Edge::Falling => wpihal_sys::HAL_ReadInterruptFallingTimestamp(
+                irq_handle,
+                std::ptr::from_mut(&mut status),
+            ),
+        }
+    };
+    InterruptError::from_status(status)?;
+    // the FPGA only latches the lower 32 bits of the timestamp
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let time =
+        unsafe { wpihal_sys::HAL_ExpandFPGATime(lower as u32, std::ptr::from_mut(&mut status)) };
+    InterruptError::from_status(status)?;
+    Ok(time)
+}
+
+/// Resolves once an edge of the given type is counted after the future was created.
+pub(crate) struct EdgeFuture {
+    interrupt: Arc<Interrupt>,
+    edge: EdgeType,
+    rising: u64,
+    falling: u64,
+}
+
+impl Future for EdgeFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // register before checking, so an edge counted in between still wakes us
+        self.interrupt.waker.register(cx.waker());
+        let rose = self.interrupt.rising.load(Ordering::Acquire) != self.rising;
+        let fell = self.interrupt.falling.load(Ordering::Acquire) != self.falling;
+        let done = match self.edge {
+            EdgeType::Rising => rose,
+            EdgeType::Falling => fell,
+            EdgeType::Either => rose || fell,
         };
-        if asserted != 0 {
-            break Ok(());
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
         }
-        InterruptError::from_status(status)?;
-    })
-    .await
-}
-
-async fn wait_for_blocking<R: Send>(f: impl FnOnce() -> R + Send) -> R {
-    let (tx, rx) = oneshot::channel();
-    std::thread::scope(|scope| {
-        scope.spawn(move || {
-            let out = f();
-            tx.send(out).ok().unwrap();
-        });
-    });
-    rx.await.unwrap()
+    }
+}
+
+impl Drop for EdgeFuture {
+    fn drop(&mut self) {
+        self.interrupt.waker.take();
+    }
+}
+
+pub(crate) async fn wait_for_edge(source: Source, edge: EdgeType) -> Result<(), InterruptError> {
+    interrupt(source)?.edge(edge).await;
+    Ok(())
+}
+
+/// A stream of timestamped edges on a digital input. The stream borrows the
+/// input for as long as it exists, and yields the input's error type.
+///
+/// Edges are queued by the reactor's dispatcher thread, so none are missed while
+/// the stream isn't being polled.
+pub struct EdgeStream<'a, E = crate::dio::DioError> {
+    interrupt: Arc<Interrupt>,
+    _source: PhantomData<&'a mut ()>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<'a, E> EdgeStream<'a, E> {
+    pub(crate) fn new(source: Source) -> Result<Self, InterruptError> {
+        let interrupt = interrupt(source)?;
+        *interrupt.events.lock().unwrap() = Some(VecDeque::new());
+        Ok(Self {
+            interrupt,
+            _source: PhantomData,
+            _error: PhantomData,
+        })
+    }
+}
+
+impl<'a, E: From<InterruptError>> Stream for EdgeStream<'a, E> {
+    type Item = Result<EdgeEvent, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.interrupt.waker.register(cx.waker());
+        let event = self
+            .interrupt
+            .events
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(VecDeque::pop_front);
+        match event {
+            Some(event) => Poll::Ready(Some(event.map_err(E::from))),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, E> Drop for EdgeStream<'a, E> {
+    fn drop(&mut self) {
+        *self.interrupt.events.lock().unwrap() = None;
+        self.interrupt.waker.take();
+    }
 }
@@ -34,3 +34,12 @@ pub mod driver_station;
 // the IRQ corresponding to the index is used for the signal's rising edge
 // the IRQ corresponding to the index + 8 is used for the signal's falling edge
 // unsure, but maybe HAL_SetInterruptUpSourceEdge works by disabling one or both of these IRQs
+//
+// THE DISPATCHER
+// each digital source that gets waited on is given a persistent interrupt from the pool of 8,
+// which listens for both edges and is cleaned up when the source is freed
+// a single dispatcher thread calls HAL_WaitForMultipleInterrupts with the IRQs of every
+// registered interrupt, counts the edges that fired and wakes the waiting task
+// futures compare the edge counts against a snapshot, so dropping one is always safe
+// HAL_WaitForMultipleInterrupts fails if another thread is waiting on one of the same IRQs,
+// so nothing else may call HAL_WaitForInterrupt on the registered interrupts
@@ -0,0 +1,326 @@
+//! Relay outputs, for Spike-style relay modules.
+//!
+//! Each of the roboRIO's four relay headers has a forward and a reverse output. A relay
+//! channel can be configured to use either or both of them, which is encoded as a typestate.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use thiserror::Error;
+
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum RelayError {
+    #[error("relay is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("channel is out of range")]
+    OutOfRange,
+    #[error("state is not valid for the relay's direction")]
+    InvalidState,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl RelayError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(RelayError::ResourceAlreadyAllocated),
+            wpihal_sys::RESOURCE_OUT_OF_RANGE => Err(RelayError::OutOfRange),
+            a => Err(RelayError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+/// The state of a relay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayState {
+    /// Both outputs are off
+    Off,
+    /// All configured outputs are on. For a bidirectional relay, both outputs are on.
+    On,
+    /// Only the forward output is on
+    Forward,
+    /// Only the reverse output is on
+    Reverse,
+}
+
+pub trait RelayDirection: crate::Sealed {
+    /// Whether the forward output is used
+    const FORWARD: bool;
+    /// Whether the reverse output is used
+    const REVERSE: bool;
+}
+
+/// Forward output only (typestate)
+pub struct ForwardOnly;
+
+/// Reverse output only (typestate)
+pub struct ReverseOnly;
+
+/// Both outputs (typestate)
+pub struct Both;
+
+/// No outputs (typestate)
+pub struct Unconfigured;
+
+/// A direction with at least one output, which can be set and read.
+pub trait ConfiguredDirection: RelayDirection {}
+
+impl crate::Sealed for ForwardOnly {}
+impl RelayDirection for ForwardOnly {
+    const FORWARD: bool = true;
+    const REVERSE: bool = false;
+}
+impl ConfiguredDirection for ForwardOnly {}
+
+impl crate::Sealed for ReverseOnly {}
+impl RelayDirection for ReverseOnly {
+    const FORWARD: bool = false;
+    const REVERSE: bool = true;
+}
+impl ConfiguredDirection for ReverseOnly {}
+
+impl crate::Sealed for Both {}
+impl RelayDirection for Both {
+    const FORWARD: bool = true;
+    const REVERSE: bool = true;
+}
+impl ConfiguredDirection for Both {}
+
+impl crate::Sealed for Unconfigured {}
+impl RelayDirection for Unconfigured {
+    const FORWARD: bool = false;
+    const REVERSE: bool = false;
+}
+
+/// Represents a relay channel.
+/// N is the relay header number, 0..=3.
+pub struct Relay<const N: u8, DIR: RelayDirection = Unconfigured> {
+    // 0 if the output isn't used
+    forward: wpihal_sys::HAL_RelayHandle,
+    reverse: wpihal_sys::HAL_RelayHandle,
+    _direction: PhantomData<DIR>,
+}
+
+fn initialize(channel: u8, forward: bool) -> Result<wpihal_sys::HAL_RelayHandle, RelayError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let handle = unsafe {
+        wpihal_sys::HAL_InitializeRelayPort(
+            wpihal_sys::HAL_GetPort(channel.into()),
+            i32::from(forward),
+            c"".as_ptr(),
+            std::ptr::from_mut(&mut status),
+        )
+    };
+    RelayError::from_status(status)?;
+    Ok(handle)
+}
+
+fn set(handle: wpihal_sys::HAL_RelayHandle, on: bool) -> Result<(), RelayError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe { wpihal_sys::HAL_SetRelay(handle, i32::from(on), std::ptr::from_mut(&mut status)) };
+    RelayError::from_status(status)
+}
+
+fn get(handle: wpihal_sys::HAL_RelayHandle) -> Result<bool, RelayError> {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    let on = unsafe { wpihal_sys::HAL_GetRelay(handle, std::ptr::from_mut(&mut status)) };
+    RelayError::from_status(status)?;
+    Ok(on != 0)
+}
+
+impl<const N: u8, DIR: RelayDirection> Relay<N, DIR> {
+    const _VALID: () = assert!(N < 4);
+
+    pub(crate) fn new_uninit() -> Relay<N, Unconfigured> {
+        let () = Self::_VALID;
+        Relay {
+            forward: 0,
+            reverse: 0,
+            _direction: PhantomData,
+        }
+    }
+
+    /// Allocates the outputs used by the new direction. On failure, the relay is returned
+    /// unconfigured, as its previous outputs have been freed.
+    fn configure<NEW: RelayDirection>(
+        self,
+    ) -> Result<Relay<N, NEW>, (RelayError, Relay<N, Unconfigured>)> {
+        // free the current outputs first, so they can be reallocated below
+        let unconfigured = self.into_unconfigured();
+        let forward = if NEW::FORWARD {
+            match initialize(N, true) {
+                Ok(handle) => handle,
+                Err(e) => return Err((e, unconfigured)),
+            }
+        } else {
+            0
+        };
+        let relay = Relay {
+            forward,
+            reverse: 0,
+            _direction: PhantomData,
+        };
+        let reverse = if NEW::REVERSE {
+            match initialize(N, false) {
+                Ok(handle) => handle,
+                // dropping the partially configured relay frees its forward output
+                Err(e) => return Err((e, unconfigured)),
+            }
+        } else {
+            0
+        };
+        Ok(Relay { reverse, ..relay })
+    }
+
+    /// Configures the relay to use only its forward output.
+    /// # Panics
+    /// Panics if the relay can't be allocated. See [`Relay::try_into_forward_only`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn into_forward_only(self) -> Relay<N, ForwardOnly> {
+        self.try_into_forward_only()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize relay {N}: {e}"))
+    }
+
+    /// Configures the relay to use only its forward output.
+    /// # Errors
+    /// Returns [`RelayError::ResourceAlreadyAllocated`] if the relay is already in use.
+    /// The relay is returned unconfigured with the error.
+    pub fn try_into_forward_only(
+        self,
+    ) -> Result<Relay<N, ForwardOnly>, (RelayError, Relay<N, Unconfigured>)> {
+        self.configure()
+    }
+
+    /// Configures the relay to use only its reverse output.
+    /// # Panics
+    /// Panics if the relay can't be allocated. See [`Relay::try_into_reverse_only`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn into_reverse_only(self) -> Relay<N, ReverseOnly> {
+        self.try_into_reverse_only()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize relay {N}: {e}"))
+    }
+
+    /// Configures the relay to use only its reverse output.
+    /// # Errors
+    /// Returns [`RelayError::ResourceAlreadyAllocated`] if the relay is already in use.
+    /// The relay is returned unconfigured with the error.
+    pub fn try_into_reverse_only(
+        self,
+    ) -> Result<Relay<N, ReverseOnly>, (RelayError, Relay<N, Unconfigured>)> {
+        self.configure()
+    }
+
+    /// Configures the relay to use both of its outputs.
+    /// # Panics
+    /// Panics if the relay can't be allocated. See [`Relay::try_into_both`]
+    /// for a non-panicking version.
+    #[must_use]
+    pub fn into_both(self) -> Relay<N, Both> {
+        self.try_into_both()
+            .unwrap_or_else(|(e, _)| panic!("failed to initialize relay {N}: {e}"))
+    }
+
+    /// Configures the relay to use both of its outputs.
+    /// # Errors
+    /// Returns [`RelayError::ResourceAlreadyAllocated`] if the relay is already in use.
+    /// The relay is returned unconfigured with the error.
+    pub fn try_into_both(self) -> Result<Relay<N, Both>, (RelayError, Relay<N, Unconfigured>)> {
+        self.configure()
+    }
+
+    /// Frees the relay's outputs.
+    #[must_use]
+    pub fn into_unconfigured(self) -> Relay<N, Unconfigured> {
+        drop(self);
+        Relay::<N, Unconfigured>::new_uninit()
+    }
+}
+
+impl<const N: u8, DIR: ConfiguredDirection> Relay<N, DIR> {
+    /// Sets the state of the relay.
+    /// # Errors
+    /// Returns [`RelayError::InvalidState`] if the state needs an output which isn't configured,
+    /// e.g. [`RelayState::Reverse`] on a forward-only relay.
+    pub fn set(&mut self, state: RelayState) -> Result<(), RelayError> {
+        let (forward, reverse) = match state {
+            RelayState::Off => (false, false),
+            RelayState::On => (DIR::FORWARD, DIR::REVERSE),
+            RelayState::Forward if DIR::FORWARD => (true, false),
+            RelayState::Reverse if DIR::REVERSE => (false, true),
+            RelayState::Forward | RelayState::Reverse => return Err(RelayError::InvalidState),
+        };
+        if DIR::FORWARD {
+            set(self.forward, forward)?;
+        }
+        if DIR::REVERSE {
+            set(self.reverse, reverse)?;
+        }
+        Ok(())
+    }
+
+    /// Gets the current state of the relay. A single-direction relay reports
+    /// [`RelayState::On`] rather than [`RelayState::Forward`] or [`RelayState::Reverse`].
+    /// # Errors
+    /// Returns an error if wpihal fails to read the relay.
+    pub fn get(&self) -> Result<RelayState, RelayError> {
+        let forward = DIR::FORWARD && get(self.forward)?;
+        let reverse = DIR::REVERSE && get(self.reverse)?;
+        Ok(match (forward, reverse) {
+            (false, false) => RelayState::Off,
+            (true, true) => RelayState::On,
+            (true, false) if !DIR::REVERSE => RelayState::On,
+            (false, true) if !DIR::FORWARD => RelayState::On,
+            (true, false) => RelayState::Forward,
+            (false, true) => RelayState::Reverse,
+        })
+    }
+}
+
+impl<const N: u8, DIR: RelayDirection> Drop for Relay<N, DIR> {
+    fn drop(&mut self) {
+        for handle in [self.forward, self.reverse] {
+            if handle != 0 {
+                unsafe { wpihal_sys::HAL_FreeRelayPort(handle) }
+            }
+        }
+    }
+}
+
+pub type Relay0 = Relay<0>;
+pub type Relay1 = Relay<1>;
+pub type Relay2 = Relay<2>;
+pub type Relay3 = Relay<3>;
+
+pub struct RelayPort {
+    pub relay0: Relay0,
+    pub relay1: Relay1,
+    pub relay2: Relay2,
+    pub relay3: Relay3,
+}
+
+static PORT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl RelayPort {
+    pub fn take() -> Option<Self> {
+        let previously_taken = PORT_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self {
+                relay0: Relay0::new_uninit(),
+                relay1: Relay1::new_uninit(),
+                relay2: Relay2::new_uninit(),
+                relay3: Relay3::new_uninit(),
+            })
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Motor safety: stops actuators which aren't updated often enough.
+//!
+//! An actuator wrapped in a [`Watchdog`] must be set at least once per expiration period,
+//! which feeds the watchdog. If the task driving it hangs or deadlocks, a background thread
+//! stops the actuator and reports the expiration to the Driver Station. If the task panics
+//! or is cancelled, dropping the watchdog stops the actuator.
+//!
+//! A watchdog doesn't expire until it has been fed for the first time, so actuators which
+//! are never set aren't reported.
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    ffi::CString,
+    mem::ManuallyDrop,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    thread::Thread,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use uom::si::f64::ElectricPotential;
+
+use crate::motor::{AnyMotorController, MotorController, MotorGroup, PwmMotorController};
+use crate::pneumatics::Solenoid;
+use crate::pneumatics::{
+    AnyDoubleSolenoid, AnySolenoid, ChannelErasedDoubleSolenoid, ChannelErasedSolenoid,
+    DoubleSolenoid, DoubleSolenoidState, InvalidDoubleSolenoidState, SolenoidChannel,
+    SolenoidController, TypedDoubleSolenoid, TypedSolenoid,
+};
+use crate::pwm::PwmChannel;
+
+/// The expiration period used by [`Watchdog::new`], matching WPILib's default
+pub const DEFAULT_EXPIRATION: Duration = Duration::from_millis(100);
+
+/// How often the watchdog thread checks for expired actuators
+const CHECK_PERIOD: Duration = Duration::from_millis(20);
+
+/// An actuator which can be put in a safe state by a [`Watchdog`].
+pub trait SafeStop {
+    /// Puts the actuator in a safe state, e.g. stops a motor or releases a solenoid.
+    fn safe_stop(&mut self);
+}
+
+/// Something registered with the watchdog thread
+trait Watched: Send + Sync {
+    fn check(&self, now: Instant);
+}
+
+static CHECKER: Lazy<Checker> = Lazy::new(Checker::new);
+
+/// Holds every live watchdog, and the thread which checks them.
+struct Checker {
+    watched: Arc<Mutex<Vec<Weak<dyn Watched>>>>,
+    thread: Thread,
+}
+
+impl Checker {
+    fn new() -> Self {
+        let watched = Arc::new(Mutex::new(Vec::new()));
+        let watched2 = Arc::clone(&watched);
+        let thread = std::thread::spawn(move || check(&watched2))
+            .thread()
+            .clone();
+        Self { watched, thread }
+    }
+
+    fn register(&self, watched: Weak<dyn Watched>) {
+        lock(&self.watched).push(watched);
+        self.thread.unpark();
+    }
+}
+
+fn check(watched: &Mutex<Vec<Weak<dyn Watched>>>) {
+    loop {
+        let live: Vec<_> = {
+            let mut watched = lock(watched);
+            watched.retain(|w| w.strong_count() > 0);
+            watched.iter().filter_map(Weak::upgrade).collect()
+        };
+        if live.is_empty() {
+            // unparked when a watchdog is registered
+            std::thread::park();
+            continue;
+        }
+        let now = Instant::now();
+        for watched in live {
+            watched.check(now);
+        }
+        std::thread::sleep(CHECK_PERIOD);
+    }
+}
+
+/// Locks a mutex, ignoring poisoning: a task panicking while setting an actuator
+/// is exactly the case where it still needs to be stopped.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn report_expiration(name: &str) {
+    let details = CString::new(format!("{name}: output not updated often enough"))
+        .unwrap_or_else(|_| c"output not updated often enough".to_owned());
+    unsafe {
+        wpihal_sys::HAL_SendError(1, 1, 0, details.as_ptr(), c"".as_ptr(), c"".as_ptr(), 1);
+    }
+}
+
+struct Feed {
+    // None until the watchdog is first fed
+    deadline: Option<Instant>,
+    expiration: Duration,
+    enabled: bool,
+    // whether the actuator was stopped since it was last fed
+    stopped: bool,
+}
+
+struct Entry<A> {
+    name: String,
+    feed: Mutex<Feed>,
+    actuator: Mutex<A>,
+}
+
+impl<A: SafeStop + Send> Watched for Entry<A> {
+    fn check(&self, now: Instant) {
+        let expired =
+            |feed: &Feed| feed.enabled && !feed.stopped && feed.deadline.is_some_and(|d| now > d);
+        if !expired(&lock(&self.feed)) {
+            return;
+        }
+        // lock the actuator before re-checking, so a set which feeds the watchdog in between
+        // either prevents the stop or is applied after it
+        let mut actuator = lock(&self.actuator);
+        let mut feed = lock(&self.feed);
+        if expired(&feed) {
+            feed.stopped = true;
+            drop(feed);
+            actuator.safe_stop();
+            drop(actuator);
+            report_expiration(&self.name);
+        }
+    }
+}
+
+/// Wraps an actuator so that it's stopped if it isn't set at least once per expiration
+/// period. Implements the same actuator traits as the wrapped actuator, and setting it
+/// through any of them feeds the watchdog.
+///
+/// The actuator is also stopped when the watchdog is dropped, e.g. if the task owning it
+/// panics or is cancelled. Use [`Watchdog::into_inner`] to release it without stopping it.
+pub struct Watchdog<A: SafeStop + Send + 'static> {
+    entry: Arc<Entry<A>>,
+}
+
+impl<A: SafeStop + Send + 'static> Watchdog<A> {
+    /// Wraps an actuator with the [`DEFAULT_EXPIRATION`] period. The name is used
+    /// when reporting an expiration to the Driver Station.
+    #[must_use]
+    pub fn new(name: impl Into<String>, actuator: A) -> Self {
+        Self::with_expiration(name, actuator, DEFAULT_EXPIRATION)
+    }
+
+    /// Wraps an actuator with the given expiration period.
+    #[must_use]
+    pub fn with_expiration(name: impl Into<String>, actuator: A, expiration: Duration) -> Self {
+        let entry = Arc::new(Entry {
+            name: name.into(),
+            feed: Mutex::new(Feed {
+                deadline: None,
+                expiration,
+                enabled: true,
+                stopped: false,
+            }),
+            actuator: Mutex::new(actuator),
+        });
+        let watched: Arc<dyn Watched> = entry.clone();
+        CHECKER.register(Arc::downgrade(&watched));
+        Self { entry }
+    }
+
+    /// Restarts the expiration period without changing the actuator's output.
+    pub fn feed(&self) {
+        let mut feed = lock(&self.entry.feed);
+        feed.deadline = Some(Instant::now() + feed.expiration);
+        feed.stopped = false;
+    }
+
+    /// Gets the expiration period.
+    #[must_use]
+    pub fn expiration(&self) -> Duration {
+        lock(&self.entry.feed).expiration
+    }
+
+    /// Sets the expiration period, which takes effect from the next feed.
+    pub fn set_expiration(&mut self, expiration: Duration) {
+        lock(&self.entry.feed).expiration = expiration;
+    }
+
+    /// Sets whether the watchdog stops the actuator when it expires.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        lock(&self.entry.feed).enabled = enabled;
+    }
+
+    /// Gets whether the watchdog has stopped the actuator since it was last fed.
+    #[must_use]
+    pub fn stopped(&self) -> bool {
+        lock(&self.entry.feed).stopped
+    }
+
+    /// Feeds the watchdog and gives access to the actuator.
+    fn actuator(&self) -> MutexGuard<'_, A> {
+        self.feed();
+        lock(&self.entry.actuator)
+    }
+
+    /// Releases the actuator from the watchdog, without stopping it. If the watchdog thread
+    /// is checking the actuator, this spins until the check finishes, which is at most as
+    /// long as stopping the actuator takes.
+    #[must_use]
+    pub fn into_inner(self) -> A {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so the entry is moved out exactly once.
+        let mut entry = unsafe { std::ptr::read(&this.entry) };
+        // the watchdog thread only holds weak references, and upgrades them briefly
+        loop {
+            match Arc::try_unwrap(entry) {
+                Ok(entry) => {
+                    return entry
+                        .actuator
+                        .into_inner()
+                        .unwrap_or_else(PoisonError::into_inner)
+                }
+                Err(shared) => {
+                    entry = shared;
+                    std::thread::yield_now();
+                }
+            }
+        }
+    }
+}
+
+impl<A: SafeStop + Send + 'static> Drop for Watchdog<A> {
+    fn drop(&mut self) {
+        lock(&self.entry.actuator).safe_stop();
+    }
+}
+
+impl<A: SafeStop + MotorController + Send + 'static> MotorController for Watchdog<A> {
+    fn set(&mut self, output: f64) {
+        self.actuator().set(output);
+    }
+
+    fn get(&self) -> f64 {
+        lock(&self.entry.actuator).get()
+    }
+
+    fn set_voltage(&mut self, voltage: ElectricPotential) {
+        self.actuator().set_voltage(voltage);
+    }
+
+    fn stop(&mut self) {
+        self.actuator().stop();
+    }
+
+    fn set_inverted(&mut self, inverted: bool) {
+        lock(&self.entry.actuator).set_inverted(inverted);
+    }
+
+    fn inverted(&self) -> bool {
+        lock(&self.entry.actuator).inverted()
+    }
+}
+
+impl<A: SafeStop + Solenoid + Send + 'static> Solenoid for Watchdog<A> {
+    fn get(&self) -> bool {
+        lock(&self.entry.actuator).get()
+    }
+
+    fn set(&mut self, state: bool) {
+        self.actuator().set(state);
+    }
+}
+
+impl<A: SafeStop + DoubleSolenoid + Send + 'static> DoubleSolenoid for Watchdog<A> {
+    fn get(&self) -> Result<DoubleSolenoidState, InvalidDoubleSolenoidState> {
+        lock(&self.entry.actuator).get()
+    }
+
+    fn set(&mut self, state: DoubleSolenoidState) {
+        self.actuator().set(state);
+    }
+}
+
+impl SafeStop for AnyMotorController {
+    fn safe_stop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<M: MotorController> SafeStop for MotorGroup<M> {
+    fn safe_stop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<C: PwmChannel> SafeStop for PwmMotorController<C> {
+    fn safe_stop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<Channel: SolenoidChannel> SafeStop for TypedSolenoid<Channel> {
+    fn safe_stop(&mut self) {
+        self.set(false);
+    }
+}
+
+impl<Controller: SolenoidController> SafeStop for ChannelErasedSolenoid<Controller> {
+    fn safe_stop(&mut self) {
+        self.set(false);
+    }
+}
+
+impl SafeStop for AnySolenoid {
+    fn safe_stop(&mut self) {
+        self.set(false);
+    }
+}
+
+impl<Controller, ForwardChannel, BackwardChannel> SafeStop
+    for TypedDoubleSolenoid<Controller, ForwardChannel, BackwardChannel>
+where
+    Controller: SolenoidController,
+    ForwardChannel: SolenoidChannel<Controller = Controller>,
+    BackwardChannel: SolenoidChannel<Controller = Controller>,
+{
+    fn safe_stop(&mut self) {
+        self.set(DoubleSolenoidState::Off);
+    }
+}
+
+impl<Controller: SolenoidController> SafeStop for ChannelErasedDoubleSolenoid<Controller> {
+    fn safe_stop(&mut self) {
+        self.set(DoubleSolenoidState::Off);
+    }
+}
+
+impl SafeStop for AnyDoubleSolenoid {
+    fn safe_stop(&mut self) {
+        self.set(DoubleSolenoidState::Off);
+    }
+}
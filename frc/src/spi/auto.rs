@@ -0,0 +1,314 @@
+//! The FPGA's auto-SPI engine.
+//!
+//! Auto-SPI repeatedly sends the same data on an SPI port, either periodically or when a
+//! digital source has an edge, and records what is received with an FPGA timestamp. This lets
+//! sensors such as gyros be sampled at kHz rates without the CPU polling them. Only one SPI
+//! port can use auto-SPI at a time.
+//!
+//! Received frames are read by a thread per [`AutoSpiStream`], which queues them for the stream.
+//! Frames can be dropped either by the FPGA, if its buffer fills, or by the stream, if it
+//! isn't polled often enough. Both are counted by [`AutoSpiStream::dropped`].
+
+#![allow(clippy::module_name_repetitions)]
+
+use std::{
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    thread::JoinHandle,
+};
+
+use futures::{channel::mpsc, Stream, StreamExt};
+use uom::si::{
+    f64::Time,
+    time::{microsecond, second},
+};
+
+use super::{Spi, SpiError, SpiPins};
+use crate::dio::DigitalSource;
+
+/// How long the reader thread waits for a frame before checking whether it should stop, in seconds
+const READ_TIMEOUT: f64 = 0.02;
+
+/// The status returned by the FPGA's DMA engine when a read times out
+const FIFO_TIMEOUT: i32 = -50400;
+
+/// How many frames are queued for a stream before frames are dropped
+const QUEUE_LENGTH: usize = 1024;
+
+/// The data received by one auto-SPI transfer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoSpiFrame {
+    /// The FPGA time at which the transfer happened
+    pub timestamp: Time,
+    /// The bytes received during the transfer
+    pub data: Vec<u8>,
+}
+
+/// An SPI port with the auto-SPI engine allocated to it.
+pub struct AutoSpi<P: SpiPins> {
+    spi: Spi<P>,
+    frame_len: usize,
+}
+
+impl<P: SpiPins> AutoSpi<P> {
+    /// Allocates the auto-SPI engine to an SPI port, with a buffer of `buffer_size` words
+    /// in the FPGA. Each frame uses one word for its timestamp, and one word per byte.
+    /// # Errors
+    /// Returns [`SpiError::ResourceAlreadyAllocated`] if another port is using auto-SPI.
+    /// The port is returned with the error, so it can still be used without auto-SPI.
+    pub fn new(spi: Spi<P>, buffer_size: usize) -> Result<Self, (SpiError, Spi<P>)> {
+        let Ok(buffer_size) = i32::try_from(buffer_size) else {
+            return Err((SpiError::ParameterOutOfRange, spi));
+        };
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_InitSPIAuto(
+                P::PORT.into(),
+                buffer_size,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        match SpiError::from_status(status) {
+            Ok(()) => Ok(Self { spi, frame_len: 0 }),
+            Err(e) => Err((e, spi)),
+        }
+    }
+
+    /// Sets the data sent by each transfer: `data`, followed by `zero_size` zeros.
+    /// # Errors
+    /// Returns [`SpiError::ParameterOutOfRange`] if the transfer is too long.
+    pub fn set_transmit_data(&mut self, data: &[u8], zero_size: usize) -> Result<(), SpiError> {
+        let data_size = i32::try_from(data.len()).map_err(|_| SpiError::ParameterOutOfRange)?;
+        let zero_size_hal = i32::try_from(zero_size).map_err(|_| SpiError::ParameterOutOfRange)?;
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_SetSPIAutoTransmitData(
+                P::PORT.into(),
+                data.as_ptr(),
+                data_size,
+                zero_size_hal,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        SpiError::from_status(status)?;
+        self.frame_len = data.len() + zero_size;
+        Ok(())
+    }
+
+    /// Configures the timing of each transfer, in ticks of the 40 MHz FPGA clock: the delay
+    /// between asserting the chip select and the first clock, and a stall inserted after every
+    /// 2^`pow2_bytes_per_read` bytes.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the transfer.
+    pub fn configure_stall(
+        &mut self,
+        cs_to_sclk_ticks: u8,
+        stall_ticks: u8,
+        pow2_bytes_per_read: u8,
+    ) -> Result<(), SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_ConfigureSPIAutoStall(
+                P::PORT.into(),
+                cs_to_sclk_ticks.into(),
+                stall_ticks.into(),
+                pow2_bytes_per_read.into(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        SpiError::from_status(status)
+    }
+
+    /// Starts a transfer every `period`, returning a stream of the received frames.
+    /// Transfers stop when the stream is dropped.
+    /// # Errors
+    /// Returns an error if wpihal fails to start auto-SPI.
+    pub fn start_rate(&mut self, period: Time) -> Result<AutoSpiStream<'_>, SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_StartSPIAutoRate(
+                P::PORT.into(),
+                period.get::<second>(),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        SpiError::from_status(status)?;
+        Ok(AutoSpiStream::new(P::PORT.into(), self.frame_len))
+    }
+
+    /// Starts a transfer on each selected edge of a digital source, returning a stream of the
+    /// received frames. Transfers stop when the stream is dropped, and the source is borrowed
+    /// until then.
+    /// # Errors
+    /// Returns an error if wpihal fails to start auto-SPI.
+    pub fn start_trigger<'a, S: DigitalSource>(
+        &'a mut self,
+        source: &'a S,
+        rising: bool,
+        falling: bool,
+    ) -> Result<AutoSpiStream<'a>, SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_StartSPIAutoTrigger(
+                P::PORT.into(),
+                source.source_handle(),
+                source.trigger_type(),
+                i32::from(rising),
+                i32::from(falling),
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        SpiError::from_status(status)?;
+        Ok(AutoSpiStream::new(P::PORT.into(), self.frame_len))
+    }
+
+    /// Frees the auto-SPI engine, returning the SPI port.
+    #[must_use]
+    pub fn into_spi(self) -> Spi<P> {
+        let this = ManuallyDrop::new(self);
+        free(P::PORT.into());
+        // SAFETY: `this` is never dropped, so the port is moved out exactly once.
+        unsafe { std::ptr::read(&this.spi) }
+    }
+}
+
+fn free(port: wpihal_sys::HAL_SPIPort) {
+    let mut status = wpihal_sys::HAL_SUCCESS;
+    unsafe { wpihal_sys::HAL_FreeSPIAuto(port, std::ptr::from_mut(&mut status)) };
+}
+
+impl<P: SpiPins> Drop for AutoSpi<P> {
+    fn drop(&mut self) {
+        free(P::PORT.into());
+    }
+}
+
+/// A stream of frames received by auto-SPI. The stream borrows the [`AutoSpi`] (and the
+/// trigger source, if any) for as long as it exists, and stops auto-SPI when dropped.
+pub struct AutoSpiStream<'a> {
+    port: wpihal_sys::HAL_SPIPort,
+    frames: mpsc::Receiver<Result<AutoSpiFrame, SpiError>>,
+    stop: Arc<AtomicBool>,
+    dropped: Arc<AtomicU64>,
+    reader: Option<JoinHandle<()>>,
+    _auto: PhantomData<&'a mut ()>,
+}
+
+impl<'a> AutoSpiStream<'a> {
+    fn new(port: wpihal_sys::HAL_SPIPort, frame_len: usize) -> Self {
+        let (tx, frames) = mpsc::channel(QUEUE_LENGTH);
+        let stop = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (stop2, dropped2) = (Arc::clone(&stop), Arc::clone(&dropped));
+        let reader =
+            std::thread::spawn(move || read_frames(port, frame_len, tx, &stop2, &dropped2));
+        Self {
+            port,
+            frames,
+            stop,
+            dropped,
+            reader: Some(reader),
+            _auto: PhantomData,
+        }
+    }
+
+    /// Gets the number of frames dropped, either by the FPGA because its buffer was full,
+    /// or because the stream wasn't polled often enough.
+    /// # Errors
+    /// Returns an error if wpihal fails to read the FPGA's dropped count.
+    pub fn dropped(&self) -> Result<u64, SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        let fpga_dropped = unsafe {
+            wpihal_sys::HAL_GetSPIAutoDroppedCount(self.port, std::ptr::from_mut(&mut status))
+        };
+        SpiError::from_status(status)?;
+        let fpga_dropped = u64::try_from(fpga_dropped).unwrap_or_default();
+        Ok(fpga_dropped + self.dropped.load(Ordering::Relaxed))
+    }
+
+    /// Starts a transfer immediately, in addition to those started by the rate or trigger.
+    /// # Errors
+    /// Returns an error if wpihal fails to start the transfer.
+    pub fn force_read(&mut self) -> Result<(), SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_ForceSPIAutoRead(self.port, std::ptr::from_mut(&mut status)) };
+        SpiError::from_status(status)
+    }
+}
+
+/// Reads frames from the FPGA until told to stop.
+fn read_frames(
+    port: wpihal_sys::HAL_SPIPort,
+    frame_len: usize,
+    mut tx: mpsc::Sender<Result<AutoSpiFrame, SpiError>>,
+    stop: &AtomicBool,
+    dropped: &AtomicU64,
+) {
+    // a timestamp word followed by one word per byte
+    let mut buffer = vec![0u32; frame_len + 1];
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let words = buffer.len() as i32;
+    while !stop.load(Ordering::Relaxed) {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            wpihal_sys::HAL_ReadSPIAutoReceivedData(
+                port,
+                buffer.as_mut_ptr(),
+                words,
+                READ_TIMEOUT,
+                std::ptr::from_mut(&mut status),
+            );
+        }
+        if status == FIFO_TIMEOUT {
+            continue;
+        }
+        let frame = SpiError::from_status(status).and_then(|()| {
+            let mut status = wpihal_sys::HAL_SUCCESS;
+            let time = unsafe {
+                wpihal_sys::HAL_ExpandFPGATime(buffer[0], std::ptr::from_mut(&mut status))
+            };
+            SpiError::from_status(status)?;
+            Ok(AutoSpiFrame {
+                #[allow(clippy::cast_precision_loss)]
+                timestamp: Time::new::<microsecond>(time as f64),
+                // the received byte is in the low byte of each word
+                #[allow(clippy::cast_possible_truncation)]
+                data: buffer[1..].iter().map(|&word| word as u8).collect(),
+            })
+        });
+        if frame.is_err() {
+            std::thread::sleep(std::time::Duration::from_secs_f64(READ_TIMEOUT));
+        }
+        if let Err(e) = tx.try_send(frame) {
+            if e.is_disconnected() {
+                return;
+            }
+            dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<'a> Stream for AutoSpiStream<'a> {
+    type Item = Result<AutoSpiFrame, SpiError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.frames.poll_next_unpin(cx)
+    }
+}
+
+impl<'a> Drop for AutoSpiStream<'a> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_StopSPIAuto(self.port, std::ptr::from_mut(&mut status)) };
+    }
+}
@@ -0,0 +1,362 @@
+//! SPI ports, implementing the [`embedded_hal`] SPI traits.
+//!
+//! The roboRIO has 5 SPI ports: chip selects 0..=3 of the onboard SPI bus, and the MXP.
+//! The onboard chip selects share the bus's MOSI and MISO pins, so those are consumed by an
+//! [`OnboardSpiBus`] shared between the onboard ports. Chip selects 1..=3 are DIO channels
+//! 26..=28, and the MXP port consumes MXP pins 14..=17, so none of them can also be used as DIO.
+//!
+//! wpihal always drives a port's chip select during a transfer. [`Spi`] implements
+//! [`SpiDevice`](embedded_hal::spi::SpiDevice) by combining a whole transaction into one
+//! transfer, and implements [`SpiBus`](embedded_hal::spi::SpiBus) with one transfer per call.
+
+#![allow(clippy::module_name_repetitions)]
+
+pub mod auto;
+pub use auto::*;
+
+use std::{
+    mem::ManuallyDrop,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use embedded_hal::spi::{ErrorKind, ErrorType, Mode, Operation, Phase, Polarity};
+use thiserror::Error;
+use uom::si::{f64::Frequency, frequency::hertz};
+
+use crate::dio::{Dio14, Dio15, Dio16, Dio17, Dio26, Dio27, Dio28, Dio29, Dio30};
+use crate::error::HalError;
+
+#[derive(Error, Debug)]
+pub enum SpiError {
+    #[error("port is already allocated")]
+    ResourceAlreadyAllocated,
+    #[error("transfer failed")]
+    TransferFailed,
+    #[error("transfer is too long")]
+    TransferTooLong,
+    #[error("delays within a transaction are not supported")]
+    DelayUnsupported,
+    #[error("parameter is out of range")]
+    ParameterOutOfRange,
+    #[error(transparent)]
+    Hal(#[from] HalError),
+}
+
+impl SpiError {
+    pub(crate) fn from_status(status: i32) -> Result<(), Self> {
+        match crate::error::resolve_status(status) {
+            wpihal_sys::HAL_SUCCESS => Ok(()),
+            wpihal_sys::RESOURCE_IS_ALLOCATED => Err(SpiError::ResourceAlreadyAllocated),
+            wpihal_sys::PARAMETER_OUT_OF_RANGE => Err(SpiError::ParameterOutOfRange),
+            a => Err(SpiError::Hal(HalError::new(a))),
+        }
+    }
+}
+
+impl embedded_hal::spi::Error for SpiError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// The roboRIO's SPI ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiPort {
+    OnboardCs0,
+    OnboardCs1,
+    OnboardCs2,
+    OnboardCs3,
+    Mxp,
+}
+
+impl From<SpiPort> for wpihal_sys::HAL_SPIPort {
+    fn from(value: SpiPort) -> Self {
+        match value {
+            SpiPort::OnboardCs0 => wpihal_sys::HAL_SPIPort_HAL_SPI_kOnboardCS0,
+            SpiPort::OnboardCs1 => wpihal_sys::HAL_SPIPort_HAL_SPI_kOnboardCS1,
+            SpiPort::OnboardCs2 => wpihal_sys::HAL_SPIPort_HAL_SPI_kOnboardCS2,
+            SpiPort::OnboardCs3 => wpihal_sys::HAL_SPIPort_HAL_SPI_kOnboardCS3,
+            SpiPort::Mxp => wpihal_sys::HAL_SPIPort_HAL_SPI_kMXP,
+        }
+    }
+}
+
+/// The polarity of a port's chip select.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipSelectPolarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// The pins of an SPI port, which are consumed by an [`Spi`] port.
+pub trait SpiPins: crate::Sealed {
+    const PORT: SpiPort;
+}
+
+/// A chip select of the onboard SPI bus.
+pub trait OnboardChipSelect: crate::Sealed {
+    const PORT: SpiPort;
+}
+
+/// Chip select 0 of the onboard SPI bus, which has no DIO channel.
+pub struct OnboardCs0 {
+    _private: (),
+}
+
+static CS0_TAKEN: AtomicBool = AtomicBool::new(false);
+
+impl OnboardCs0 {
+    pub fn take() -> Option<Self> {
+        let previously_taken = CS0_TAKEN.swap(true, Ordering::Relaxed);
+        if previously_taken {
+            None
+        } else {
+            Some(Self { _private: () })
+        }
+    }
+}
+
+impl crate::Sealed for OnboardCs0 {}
+impl OnboardChipSelect for OnboardCs0 {
+    const PORT: SpiPort = SpiPort::OnboardCs0;
+}
+
+impl OnboardChipSelect for Dio26 {
+    const PORT: SpiPort = SpiPort::OnboardCs1;
+}
+
+impl OnboardChipSelect for Dio27 {
+    const PORT: SpiPort = SpiPort::OnboardCs2;
+}
+
+impl OnboardChipSelect for Dio28 {
+    const PORT: SpiPort = SpiPort::OnboardCs3;
+}
+
+/// The MOSI and MISO pins of the onboard SPI bus, shared by its chip selects.
+pub struct OnboardSpiBus {
+    _mosi: Dio29,
+    _miso: Dio30,
+}
+
+impl OnboardSpiBus {
+    /// Claims the onboard bus's data pins, so they can be shared between chip selects.
+    #[must_use]
+    pub fn new(mosi: Dio29, miso: Dio30) -> Arc<Self> {
+        Arc::new(Self {
+            _mosi: mosi,
+            _miso: miso,
+        })
+    }
+}
+
+/// The pins of an onboard SPI port: one chip select, and the shared bus.
+pub struct OnboardSpiPins<CS: OnboardChipSelect> {
+    pub cs: CS,
+    pub bus: Arc<OnboardSpiBus>,
+}
+
+impl<CS: OnboardChipSelect> crate::Sealed for OnboardSpiPins<CS> {}
+impl<CS: OnboardChipSelect> SpiPins for OnboardSpiPins<CS> {
+    const PORT: SpiPort = CS::PORT;
+}
+
+/// The pins of the MXP SPI port: CS, CLK, MISO and MOSI on MXP pins 14..=17.
+pub type MxpSpiPins = (Dio14, Dio15, Dio16, Dio17);
+
+impl crate::Sealed for MxpSpiPins {}
+impl SpiPins for MxpSpiPins {
+    const PORT: SpiPort = SpiPort::Mxp;
+}
+
+fn hal_len(len: usize) -> Result<i32, SpiError> {
+    i32::try_from(len).map_err(|_| SpiError::TransferTooLong)
+}
+
+/// Transfers `tx` while receiving into `rx`, with the chip select asserted throughout.
+fn transfer(port: SpiPort, tx: &[u8], rx: &mut [u8]) -> Result<(), SpiError> {
+    debug_assert_eq!(tx.len(), rx.len());
+    let result = unsafe {
+        wpihal_sys::HAL_TransactionSPI(
+            port.into(),
+            tx.as_ptr(),
+            rx.as_mut_ptr(),
+            hal_len(tx.len())?,
+        )
+    };
+    if result < 0 {
+        Err(SpiError::TransferFailed)
+    } else {
+        Ok(())
+    }
+}
+
+/// An SPI port. Defaults to 500 kHz, mode 0, and an active-low chip select.
+pub struct Spi<P: SpiPins> {
+    pins: P,
+}
+
+impl<P: SpiPins> Spi<P> {
+    /// Opens the SPI port.
+    /// # Errors
+    /// Returns [`SpiError::ResourceAlreadyAllocated`] if the port is already open.
+    /// The pins are returned with the error.
+    pub fn new(pins: P) -> Result<Self, (SpiError, P)> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe { wpihal_sys::HAL_InitializeSPI(P::PORT.into(), std::ptr::from_mut(&mut status)) };
+        match SpiError::from_status(status) {
+            Ok(()) => Ok(Self { pins }),
+            Err(e) => Err((e, pins)),
+        }
+    }
+
+    /// Gets the port this is.
+    #[must_use]
+    pub fn port(&self) -> SpiPort {
+        P::PORT
+    }
+
+    /// Sets the clock rate. The maximum is 4 MHz.
+    pub fn set_speed(&mut self, speed: Frequency) {
+        #[allow(clippy::cast_possible_truncation)]
+        let speed = speed.get::<hertz>().round() as i32;
+        unsafe { wpihal_sys::HAL_SetSPISpeed(P::PORT.into(), speed) };
+    }
+
+    /// Sets the clock polarity and phase.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let mode = match (mode.polarity, mode.phase) {
+            (Polarity::IdleLow, Phase::CaptureOnFirstTransition) => {
+                wpihal_sys::HAL_SPIMode_HAL_SPI_kMode0
+            }
+            (Polarity::IdleLow, Phase::CaptureOnSecondTransition) => {
+                wpihal_sys::HAL_SPIMode_HAL_SPI_kMode1
+            }
+            (Polarity::IdleHigh, Phase::CaptureOnFirstTransition) => {
+                wpihal_sys::HAL_SPIMode_HAL_SPI_kMode2
+            }
+            (Polarity::IdleHigh, Phase::CaptureOnSecondTransition) => {
+                wpihal_sys::HAL_SPIMode_HAL_SPI_kMode3
+            }
+        };
+        unsafe { wpihal_sys::HAL_SetSPIMode(P::PORT.into(), mode) };
+    }
+
+    /// Sets the polarity of the chip select.
+    /// # Errors
+    /// Returns an error if wpihal fails to configure the chip select.
+    pub fn set_chip_select_polarity(
+        &mut self,
+        polarity: ChipSelectPolarity,
+    ) -> Result<(), SpiError> {
+        let mut status = wpihal_sys::HAL_SUCCESS;
+        unsafe {
+            match polarity {
+                ChipSelectPolarity::ActiveLow => wpihal_sys::HAL_SetSPIChipSelectActiveLow(
+                    P::PORT.into(),
+                    std::ptr::from_mut(&mut status),
+                ),
+                ChipSelectPolarity::ActiveHigh => wpihal_sys::HAL_SetSPIChipSelectActiveHigh(
+                    P::PORT.into(),
+                    std::ptr::from_mut(&mut status),
+                ),
+            }
+        }
+        SpiError::from_status(status)
+    }
+
+    /// Closes the SPI port, returning its pins.
+    #[must_use]
+    pub fn into_pins(self) -> P {
+        let this = ManuallyDrop::new(self);
+        unsafe { wpihal_sys::HAL_CloseSPI(P::PORT.into()) };
+        // SAFETY: `this` is never dropped, so the pins are moved out exactly once.
+        unsafe { std::ptr::read(&this.pins) }
+    }
+}
+
+impl<P: SpiPins> Drop for Spi<P> {
+    fn drop(&mut self) {
+        unsafe { wpihal_sys::HAL_CloseSPI(P::PORT.into()) };
+    }
+}
+
+impl<P: SpiPins> ErrorType for Spi<P> {
+    type Error = SpiError;
+}
+
+/// Each call is a separate transfer, during which the port's chip select is asserted.
+impl<P: SpiPins> embedded_hal::spi::SpiBus for Spi<P> {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        let tx = vec![0; words.len()];
+        transfer(P::PORT, &tx, words)
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        let mut rx = vec![0; words.len()];
+        transfer(P::PORT, words, &mut rx)
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        let len = read.len().max(write.len());
+        let mut tx = write.to_vec();
+        tx.resize(len, 0);
+        let mut rx = vec![0; len];
+        transfer(P::PORT, &tx, &mut rx)?;
+        read.copy_from_slice(&rx[..read.len()]);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        let tx = words.to_vec();
+        transfer(P::PORT, &tx, words)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// The whole transaction is combined into one transfer, so the chip select stays asserted
+/// throughout. Delays can't be inserted into a transfer, so transactions containing
+/// [`Operation::DelayNs`] fail with [`SpiError::DelayUnsupported`].
+impl<P: SpiPins> embedded_hal::spi::SpiDevice for Spi<P> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut tx = Vec::new();
+        for operation in operations.iter() {
+            match operation {
+                Operation::Read(words) => tx.resize(tx.len() + words.len(), 0),
+                Operation::Write(words) => tx.extend_from_slice(words),
+                Operation::Transfer(read, write) => {
+                    let start = tx.len();
+                    tx.extend_from_slice(write);
+                    tx.resize(start + read.len().max(write.len()), 0);
+                }
+                Operation::TransferInPlace(words) => tx.extend_from_slice(words),
+                Operation::DelayNs(_) => return Err(SpiError::DelayUnsupported),
+            }
+        }
+        let mut rx = vec![0; tx.len()];
+        transfer(P::PORT, &tx, &mut rx)?;
+        let mut rx = rx.as_slice();
+        for operation in operations {
+            let len = match operation {
+                Operation::Read(words) | Operation::TransferInPlace(words) => {
+                    words.copy_from_slice(&rx[..words.len()]);
+                    words.len()
+                }
+                Operation::Write(words) => words.len(),
+                Operation::Transfer(read, write) => {
+                    read.copy_from_slice(&rx[..read.len()]);
+                    read.len().max(write.len())
+                }
+                Operation::DelayNs(_) => 0,
+            };
+            rx = &rx[len..];
+        }
+        Ok(())
+    }
+}
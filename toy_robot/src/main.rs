@@ -1,8 +1,9 @@
 use std::sync::OnceLock;
 
+use embedded_hal::digital::InputPin;
 use embedded_hal_async::digital::Wait;
 use frc::{
-    dio::DioPort,
+    dio::{AnyInput, DioPort},
     pneumatics::{
         ctre_pcm::{CtrePcm, CtrePneumatics},
         rev_ph::{RevPh, RevPneumatics},
@@ -49,13 +50,25 @@ async fn main() {
     ];
 
     let ds = DS.get_or_init(DriverStation::new);
-    let DioPort { dio1, .. } = DioPort::take().unwrap();
+    let DioPort {
+        dio1,
+        dio2,
+        dio3,
+        dio4,
+        ..
+    } = DioPort::take().unwrap();
+    let sensor_bank = [
+        dio2.into_input().erase(),
+        dio3.into_input().erase(),
+        dio4.into_input().erase(),
+    ];
 
     tokio::spawn(tie_solenoid_to_limit_switch(
         TypedSolenoid::new(rev2),
         dio1.into_input(),
     ));
     tokio::spawn(fire_solenoids_on_button(ds, double_solenoid_array));
+    tokio::spawn(report_sensor_bank(ds, sensor_bank));
     std::future::pending::<()>().await;
 }
 
@@ -87,3 +100,34 @@ async fn fire_solenoids_on_button<const N: usize>(
         ds.wait_for_packet().await;
     }
 }
+
+async fn report_sensor_bank<const N: usize>(
+    ds: &'static DriverStation,
+    mut sensors: [AnyInput; N],
+) {
+    // only report when the triggered channels (or a read error) change
+    let mut last_report = String::new();
+    loop {
+        let triggered: Result<Vec<u8>, _> = sensors
+            .iter_mut()
+            .filter_map(|sensor| match sensor.is_high() {
+                Ok(high) => high.then(|| Ok(sensor.channel())),
+                Err(e) => Some(Err(e)),
+            })
+            .collect();
+        let report = match triggered {
+            Ok(channels) if channels.is_empty() => String::new(),
+            Ok(channels) => format!("sensors triggered on channels {channels:?}"),
+            Err(e) => format!("failed to read sensor bank: {e}"),
+        };
+        if report != last_report {
+            if report.is_empty() {
+                println!("no sensors triggered");
+            } else {
+                println!("{report}");
+            }
+            last_report = report;
+        }
+        ds.wait_for_packet().await;
+    }
+}
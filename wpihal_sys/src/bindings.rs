@@ -3393,3 +3393,159 @@ extern "C" {
 extern "C" {
     pub fn HAL_ClearREVPHStickyFaults(handle: HAL_REVPHHandle, status: *mut i32);
 }
+extern "C" {
+    pub fn HAL_InitializeDutyCycle(
+        digitalSourceHandle: HAL_Handle,
+        triggerType: HAL_AnalogTriggerType,
+        status: *mut i32,
+    ) -> HAL_DutyCycleHandle;
+}
+extern "C" {
+    pub fn HAL_FreeDutyCycle(dutyCycleHandle: HAL_DutyCycleHandle);
+}
+extern "C" {
+    pub fn HAL_SetDutyCycleSimDevice(handle: HAL_DutyCycleHandle, device: HAL_SimDeviceHandle);
+}
+extern "C" {
+    pub fn HAL_GetDutyCycleFrequency(dutyCycleHandle: HAL_DutyCycleHandle, status: *mut i32)
+        -> i32;
+}
+extern "C" {
+    pub fn HAL_GetDutyCycleOutput(dutyCycleHandle: HAL_DutyCycleHandle, status: *mut i32) -> f64;
+}
+extern "C" {
+    pub fn HAL_GetDutyCycleHighTime(dutyCycleHandle: HAL_DutyCycleHandle, status: *mut i32) -> i32;
+}
+extern "C" {
+    pub fn HAL_GetDutyCycleOutputScaleFactor(
+        dutyCycleHandle: HAL_DutyCycleHandle,
+        status: *mut i32,
+    ) -> i32;
+}
+extern "C" {
+    pub fn HAL_GetDutyCycleFPGAIndex(dutyCycleHandle: HAL_DutyCycleHandle, status: *mut i32)
+        -> i32;
+}
+pub const HAL_kAddressableLEDMaxLength: u32 = 5460;
+#[doc = " structure for holding one LED's color data."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct HAL_AddressableLEDData {
+    #[doc = "< blue value"]
+    pub b: u8,
+    #[doc = "< green value"]
+    pub g: u8,
+    #[doc = "< red value"]
+    pub r: u8,
+    pub padding: u8,
+}
+#[test]
+fn bindgen_test_layout_HAL_AddressableLEDData() {
+    const UNINIT: ::std::mem::MaybeUninit<HAL_AddressableLEDData> =
+        ::std::mem::MaybeUninit::uninit();
+    let ptr = UNINIT.as_ptr();
+    assert_eq!(
+        ::std::mem::size_of::<HAL_AddressableLEDData>(),
+        4usize,
+        concat!("Size of: ", stringify!(HAL_AddressableLEDData))
+    );
+    assert_eq!(
+        ::std::mem::align_of::<HAL_AddressableLEDData>(),
+        1usize,
+        concat!("Alignment of ", stringify!(HAL_AddressableLEDData))
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).b) as usize - ptr as usize },
+        0usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(HAL_AddressableLEDData),
+            "::",
+            stringify!(b)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).g) as usize - ptr as usize },
+        1usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(HAL_AddressableLEDData),
+            "::",
+            stringify!(g)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).r) as usize - ptr as usize },
+        2usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(HAL_AddressableLEDData),
+            "::",
+            stringify!(r)
+        )
+    );
+    assert_eq!(
+        unsafe { ::std::ptr::addr_of!((*ptr).padding) as usize - ptr as usize },
+        3usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(HAL_AddressableLEDData),
+            "::",
+            stringify!(padding)
+        )
+    );
+}
+extern "C" {
+    pub fn HAL_InitializeAddressableLED(
+        outputPort: HAL_DigitalHandle,
+        status: *mut i32,
+    ) -> HAL_AddressableLEDHandle;
+}
+extern "C" {
+    pub fn HAL_FreeAddressableLED(handle: HAL_AddressableLEDHandle);
+}
+extern "C" {
+    pub fn HAL_SetAddressableLEDOutputPort(
+        handle: HAL_AddressableLEDHandle,
+        outputPort: HAL_DigitalHandle,
+        status: *mut i32,
+    );
+}
+extern "C" {
+    pub fn HAL_SetAddressableLEDLength(
+        handle: HAL_AddressableLEDHandle,
+        length: i32,
+        status: *mut i32,
+    );
+}
+extern "C" {
+    pub fn HAL_WriteAddressableLEDData(
+        handle: HAL_AddressableLEDHandle,
+        data: *const HAL_AddressableLEDData,
+        length: i32,
+        status: *mut i32,
+    );
+}
+extern "C" {
+    pub fn HAL_SetAddressableLEDBitTiming(
+        handle: HAL_AddressableLEDHandle,
+        highTime0NanoSeconds: i32,
+        lowTime0NanoSeconds: i32,
+        highTime1NanoSeconds: i32,
+        lowTime1NanoSeconds: i32,
+        status: *mut i32,
+    );
+}
+extern "C" {
+    pub fn HAL_SetAddressableLEDSyncTime(
+        handle: HAL_AddressableLEDHandle,
+        syncTimeMicroSeconds: i32,
+        status: *mut i32,
+    );
+}
+extern "C" {
+    pub fn HAL_StartAddressableLEDOutput(handle: HAL_AddressableLEDHandle, status: *mut i32);
+}
+extern "C" {
+    pub fn HAL_StopAddressableLEDOutput(handle: HAL_AddressableLEDHandle, status: *mut i32);
+}
@@ -21,6 +21,9 @@ pub fn generate_bindings() -> Result<(), Box<dyn Error>> {
         ])
         .header(headers_folder.join("hal/HAL.h"))
         .header(wrappers_folder.join("REVPH.h"))
+        // not included by HAL.h
+        .header(headers_folder.join("hal/DutyCycle.h"))
+        .header(headers_folder.join("hal/AddressableLED.h"))
         .allowlist_function("HAL_.*")
         .allowlist_type("HAL_.*")
         .allowlist_var("HAL_.*")